@@ -0,0 +1,87 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use yamlfun::{CompiledExpr, DefaultPlatform, Expr, Loader, Vm};
+
+const EVEN_ODD: &str = "
+:let:
+  isEven:
+    :lambda: [n]
+    :do:
+      :if: {:==: [n, :: 0]}
+      :then: {:: true}
+      :else: [isOdd, {:-: [n, :: 1]}]
+  isOdd:
+    :lambda: [n]
+    :do:
+      :if: {:==: [n, :: 0]}
+      :then: {:: false}
+      :else: [isEven, {:-: [n, :: 1]}]
+:in:
+  [isEven, {:: 1000}]
+";
+
+const FIB: &str = "
+:let:
+  fib:
+    :lambda: [n]
+    :do:
+      :if: {:<: [n, :: 2]}
+      :then: n
+      :else: {:+: [[fib, {:-: [n, :: 1]}], [fib, {:-: [n, :: 2]}]]}
+:in:
+  [fib, {:: 15}]
+";
+
+const STD: &str = "
+:|>:
+  - {:: [1, 2, 3, 4, 5]}
+  - List.tail
+  - List.head
+  - [Maybe.withDefault, {:: 0}]
+";
+
+const LIST: &str = "
+:|>:
+  - [List.range, {:: 1}, {:: 1000}]
+  - [List.map, [mul, {:: 3}]]
+  - [List.filter, [lt, {:: 100}]]
+  - [List.sortBy, [sub, {:: 0}]]
+  - [List.foldl, add, {:: 0}]
+";
+
+fn load(code: &str) -> Expr {
+    Loader::new().load(code.trim()).unwrap()
+}
+
+fn bench(c: &mut Criterion) {
+    c.bench_function("vm new", |b| b.iter(|| Vm::new(DefaultPlatform).unwrap()));
+
+    let vm = Vm::new(DefaultPlatform).unwrap();
+
+    let even_odd = load(EVEN_ODD);
+    c.bench_function("even odd 1000", |b| {
+        b.iter(|| vm.eval(black_box(even_odd.clone())).unwrap())
+    });
+
+    let fib = load(FIB);
+    c.bench_function("fib 15", |b| {
+        b.iter(|| vm.eval(black_box(fib.clone())).unwrap())
+    });
+
+    let fib = CompiledExpr::new(&fib);
+    c.bench_function("fib 15 compiled", |b| {
+        b.iter(|| vm.eval_compiled(black_box(&fib)).unwrap())
+    });
+
+    let std = load(STD);
+    c.bench_function("std functions", |b| {
+        b.iter(|| vm.eval(black_box(std.clone())).unwrap())
+    });
+
+    let list = load(LIST);
+    c.bench_function("list 1000", |b| {
+        b.iter(|| vm.eval(black_box(list.clone())).unwrap())
+    });
+}
+
+criterion_group!(benches, bench);
+criterion_main!(benches);
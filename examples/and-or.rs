@@ -0,0 +1,20 @@
+use yamlfun::{yaml, DefaultPlatform, Expr, Vm};
+
+const AND: &str = "
+:&&:
+  - [ne, x, :: null]
+  - x.enabled
+";
+
+const OR: &str = ":||: [:: true, undefined]";
+
+fn main() {
+    let vm = Vm::new(DefaultPlatform)
+        .unwrap()
+        .with_env([("x".into(), Expr::Value(Default::default()))]);
+
+    for code in [AND, OR] {
+        let expr: Expr = yaml::from_str(code.trim()).unwrap();
+        println!("{}", vm.eval(expr).unwrap());
+    }
+}
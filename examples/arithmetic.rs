@@ -0,0 +1,16 @@
+use yamlfun::{yaml, DefaultPlatform, Expr, Vm};
+
+const SUB: &str = ":-: [:: 10, :: 1, :: 2.5]";
+const MUL: &str = ":*: [:: 2, :: -3]";
+const DIV: &str = ":/: [:: 7, :: 2]";
+const MOD: &str = ":%: [:: 7, :: 2]";
+const POW: &str = ":pow: [:: 2, :: 10]";
+
+fn main() {
+    let vm = Vm::new(DefaultPlatform).unwrap();
+
+    for code in [SUB, MUL, DIV, MOD, POW] {
+        let expr: Expr = yaml::from_str(code.trim()).unwrap();
+        println!("{}", vm.eval(expr).unwrap());
+    }
+}
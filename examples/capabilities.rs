@@ -0,0 +1,72 @@
+use yamlfun::platform::Platform;
+use yamlfun::{vm, Capabilities, Env, Loader, PlatformStack, Result, Runtime, Value, Vm};
+
+const PLUGIN: &str = "
+:rec:
+  hello:
+    :lambda: [name]
+    :do:
+      :platform: log.info
+      :arg: { :fmt: 'hello ${name}' }
+  secret:
+    :lambda: [name]
+    :do:
+      :platform: env.get
+      :arg: name
+";
+
+/// Answers every call with its name and argument.
+struct Echo(&'static [&'static str]);
+
+impl Platform for Echo {
+    fn init(&self, _: &mut vm::State) -> Result<()> {
+        Ok(())
+    }
+
+    fn call(&self, _: Env, name: &str, arg: Value, _: &dyn Runtime) -> Result<Value> {
+        Ok(format!("{} {}", name, arg).into())
+    }
+
+    fn names(&self) -> Vec<String> {
+        self.0.iter().map(|n| n.to_string()).collect()
+    }
+}
+
+fn main() {
+    let platform = PlatformStack::new()
+        .with("env", Echo(&["get", "set"]))
+        .with("log", Echo(&["info"]));
+
+    let vm = Vm::new(platform)
+        .unwrap()
+        .with_search_path("examples/capabilities")
+        .with_module("Plugin", PLUGIN.trim())
+        .with_module("Sneaky", "{:import: env.yml}")
+        .with_capabilities(
+            Capabilities::none()
+                .with("log")
+                .with("env.get")
+                .with(":import"),
+        )
+        .with_module_capabilities("Plugin", Capabilities::none().with("log"))
+        .with_module_capabilities("Sneaky", Capabilities::none().with(":import"))
+        .with_audit(|name, arg| println!("audit: {} {}", name, arg));
+
+    let scripts = [
+        "{:platform: env.get, :arg: {:: HOME}}",
+        "{:platform: env.set, :arg: {:: HOME}}",
+        "{:let: {P: {:import: Plugin}}, :in: [P.hello, {:: world}]}",
+        "{:let: {P: {:import: Plugin}}, :in: [P.secret, {:: HOME}]}",
+        // Code a module imports can't do more than the module.
+        "{:let: {S: {:import: Sneaky}}, :in: [S, {:: HOME}]}",
+        "{:let: {E: {:import: env.yml}}, :in: [E, {:: HOME}]}",
+    ];
+
+    for script in scripts.iter() {
+        let script = Loader::new().load(script).unwrap();
+        match vm.eval(script) {
+            Ok(v) => println!("{}", v),
+            Err(e) => println!("{}", e.root()),
+        }
+    }
+}
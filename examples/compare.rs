@@ -0,0 +1,14 @@
+use yamlfun::{yaml, DefaultPlatform, Expr, Vm};
+
+const LT: &str = ":<: [:: 1, :: 1.5]";
+const GE: &str = ":>=: [:: [a, b], :: [a, b, c]]";
+const COMPARE: &str = "[compare, :: foo, :: bar]";
+
+fn main() {
+    let vm = Vm::new(DefaultPlatform).unwrap();
+
+    for code in [LT, GE, COMPARE] {
+        let expr: Expr = yaml::from_str(code.trim()).unwrap();
+        println!("{}", vm.eval(expr).unwrap());
+    }
+}
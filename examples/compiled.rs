@@ -0,0 +1,21 @@
+use yamlfun::{yaml, CompiledExpr, DefaultPlatform, Expr, Vm};
+
+const POLICY: &str = "
+:let:
+  user: {:: {name: alice, age: 30}}
+:in:
+  :and:
+    - {:>=: [user.age, :: 18]}
+    - {:==: [user.name, :: alice]}
+";
+
+fn main() {
+    let vm = Vm::new(DefaultPlatform).unwrap();
+
+    let policy: Expr = yaml::from_str(POLICY.trim()).unwrap();
+    let policy = CompiledExpr::new(&policy);
+
+    for _ in 0..3 {
+        println!("{}", vm.eval_compiled(&policy).unwrap());
+    }
+}
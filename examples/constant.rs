@@ -11,15 +11,15 @@ fn main() {
     let one = vm.eval(one).unwrap();
     println!("{}", one);
 
-    let foo: Expr = yaml::from_str(FOO.trim()).unwrap();
-    let foo = vm.eval(foo).unwrap();
-    println!("{}", foo);
+    let string: Expr = yaml::from_str(FOO.trim()).unwrap();
+    let string = vm.eval(string).unwrap();
+    println!("{}", string);
 
     let bar: Expr = yaml::from_str(BAR.trim()).unwrap();
     let bar = vm.eval(bar).unwrap();
     println!("{}", bar);
 
-    let baz: Expr = yaml::from_str(BAZ.trim()).unwrap();
-    let baz = vm.eval(baz).unwrap();
-    println!("{}", baz);
+    let list: Expr = yaml::from_str(BAZ.trim()).unwrap();
+    let list = vm.eval(list).unwrap();
+    println!("{}", list);
 }
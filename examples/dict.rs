@@ -0,0 +1,30 @@
+use std::convert::TryFrom;
+use yamlfun::{yaml, DefaultPlatform, Dict, Expr, Value, Vm, Yaml};
+
+const PORTS: &str = "
+80: http
+443: https
+[8000, 8080]: dev
+";
+
+const ADD_SSH: &str = "
+:lambda: [ports]
+:do:
+  - Dict.remove
+  - {:: [8000, 8080]}
+  - [Dict.insert, {:: 22}, {:: ssh}, ports]
+";
+
+fn main() {
+    let vm = Vm::new(DefaultPlatform).unwrap();
+
+    let yaml: Yaml = yaml::from_str(PORTS.trim()).unwrap();
+    let ports = Dict::try_from(yaml).unwrap();
+
+    let add_ssh: Expr = yaml::from_str(ADD_SSH.trim()).unwrap();
+    let add_ssh = vm.eval(add_ssh).unwrap();
+
+    let ports = vm.call(add_ssh, vec![Value::from(ports).into()]).unwrap();
+    println!("{}", ports);
+    print!("{}", yaml::to_string(&ports).unwrap());
+}
@@ -16,9 +16,9 @@ fn main() {
     let no = vm.eval(no).unwrap();
     println!("{}", &no);
 
-    let foo: Expr = yaml::from_str(FOO.trim()).unwrap();
-    let foo = vm.eval(foo).unwrap();
-    println!("{}", &foo);
+    let same: Expr = yaml::from_str(FOO.trim()).unwrap();
+    let same = vm.eval(same).unwrap();
+    println!("{}", &same);
 
     let bar: Expr = yaml::from_str(BAR.trim()).unwrap();
     let bar = vm.eval(bar).unwrap();
@@ -0,0 +1,17 @@
+use yamlfun::{yaml, DefaultPlatform, Expr, Vm};
+
+const GREET: &str = "
+:lambda: [user]
+:do:
+  :fmt: 'Hello ${user.name}, you have ${user.unread} new messages.'
+";
+
+fn main() {
+    let vm = Vm::new(DefaultPlatform).unwrap();
+
+    let greet: Expr = yaml::from_str(GREET.trim()).unwrap();
+    let greet = vm.eval(greet).unwrap();
+
+    let user: Expr = yaml::from_str("{:: {name: Ann, unread: 3}}").unwrap();
+    println!("{}", vm.call(greet, vec![user]).unwrap());
+}
@@ -0,0 +1,77 @@
+use std::time::Duration;
+use yamlfun::{Capabilities, CompiledExpr, FsPlatform, Loader, PlatformStack, Vm};
+
+const SCRIPT: &str = "
+:let:
+  read:
+    :lambda: [path]
+    :do:
+      :|>:
+        - :platform: fs.readYaml
+          :arg: path
+        - [Result.withDefault, { :: null }]
+  files:
+    :platform: fs.glob
+    :arg: { :: config/**/*.yml }
+:in:
+  :rec:
+    files: [Result.withDefault, { :: [] }, files]
+    configs: [List.map, read, [Result.withDefault, { :: [] }, files]]
+    cache:
+      - [Result.withDefault, null_]
+      - :platform: fs.readJson
+        :arg: { :: config/services/cache.json }
+    missing:
+      - [Result.withDefault, { :: nothing here }]
+      - :platform: fs.read
+        :arg: { :: nope.yml }
+    escaped:
+      - :platform: fs.read
+        :arg: { :: ../fs.rs }
+      - :lambda: [text]
+        :do: text
+      - :lambda: [error]
+        :do: { :fmt: 'error: ${error}' }
+";
+
+const STARS: &str = "
+:|>:
+  - :platform: fs.glob
+    :arg: { :: 'logs/*a*a*a*a*a*a*a*a*b' }
+  - [Result.withDefault, { :: [] }]
+";
+
+fn main() {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/examples/fs");
+    let platform = PlatformStack::new().with("fs", FsPlatform::new(dir).unwrap());
+
+    let vm = Vm::new(platform)
+        .unwrap()
+        .with_capabilities(Capabilities::none().with("fs"));
+
+    let script = Loader::new().load(SCRIPT.trim()).unwrap();
+    let res = vm.eval(script).unwrap();
+    println!("{}", res);
+
+    // However many stars, matching a name takes no time.
+    let vm = vm.with_timeout(Duration::from_millis(100));
+    let glob = Loader::new().load(STARS.trim()).unwrap();
+    println!("{}", vm.eval(glob).unwrap());
+
+    // And every file looked at is a step.
+    println!("{}", glob_steps(dir) > glob_steps(&format!("{}/logs", dir)));
+}
+
+/// The steps globbing every file in `dir` takes.
+fn glob_steps(dir: &str) -> u64 {
+    let platform = PlatformStack::new().with("fs", FsPlatform::new(dir).unwrap());
+    let vm = Vm::new(platform).unwrap();
+    let glob = Loader::new()
+        .load("{:platform: fs.glob, :arg: {:: '**'}}")
+        .unwrap();
+    let glob = CompiledExpr::new(&glob);
+    vm.eval_compiled(&glob).unwrap();
+    let ctx = vm.context();
+    vm.eval_with(&glob, &ctx).unwrap();
+    ctx.steps()
+}
@@ -0,0 +1,54 @@
+use std::fs;
+use yamlfun::{Capabilities, DefaultPlatform, Error, Expr, Loader, Vm};
+
+fn load(file: &str) -> Expr {
+    let code = fs::read_to_string(file).unwrap();
+    Loader::new().with_file(file).load(&code).unwrap()
+}
+
+fn main() {
+    let vm = Vm::new(DefaultPlatform)
+        .unwrap()
+        .with_search_path("examples/import/lib")
+        .with_module("Math", "{:rec: {double: {:import: double.yml}}}");
+
+    // Relative to the importing file.
+    println!("{}", vm.eval(load("examples/import/main.yml")).unwrap());
+
+    // Found in the search paths, only with the names the module exports.
+    let net = Loader::new().load("{:import: net.yml}").unwrap();
+    println!("{}", vm.eval(net).unwrap());
+
+    // Imported files see the prelude, whoever imports them first.
+    let double = Loader::new()
+        .load("{:let: {Math: {:import: Math}}, :in: [Math.double, {:: 21}]}")
+        .unwrap();
+    println!("{}", vm.eval(double).unwrap());
+
+    // Only files next to the importing file, or in a search path, can be
+    // imported.
+    let cargo = fs::canonicalize("Cargo.toml")
+        .unwrap()
+        .display()
+        .to_string();
+    let outside = [
+        (None, cargo.as_str()),
+        (None, "./Cargo.toml"),
+        (Some("examples/import/main.yml"), "../../Cargo.toml"),
+    ];
+    for (file, path) in outside.iter() {
+        let loader = file.map_or_else(Loader::new, |f| Loader::new().with_file(f));
+        let expr = loader.load(&format!("{{:import: {}}}", path)).unwrap();
+        let err = vm.eval(expr).unwrap_err();
+        println!("{}", matches!(err.root(), Error::ModuleNotFound(_)));
+    }
+
+    // Imported files are only loaded once, so they can't import themselves.
+    let err = vm.eval(load("examples/import/cycle/a.yml")).unwrap_err();
+    println!("{}", matches!(err.root(), Error::CyclicImport(_)));
+
+    // Importing files is a capability, like platform calls.
+    let vm = vm.with_capabilities(Capabilities::none());
+    let err = vm.eval(load("examples/import/main.yml")).unwrap_err();
+    println!("{}", err.root());
+}
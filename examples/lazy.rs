@@ -0,0 +1,62 @@
+use yamlfun::{yaml, CompiledExpr, DefaultPlatform, Expr, Vm};
+
+const FIB: &str = "
+:lambda: [n]
+:do:
+  :if: {:<: [n, :: 2]}
+  :then: n
+  :else: {:+: [[fib, {:-: [n, :: 1]}], [fib, {:-: [n, :: 2]}]]}
+";
+
+const ONCE: &str = "
+:let:
+  x: [fib, {:: 15}]
+:in:
+  x
+";
+
+const TEN_TIMES: &str = "
+:let:
+  x: [fib, {:: 15}]
+:in:
+  :+: [x, x, x, x, x, x, x, x, x, x]
+";
+
+const FIELD: &str = "
+:let:
+  r:
+    :rec:
+      bad: {:raise: {:: oops}}
+      good: {:: 1}
+:in:
+  r.good
+";
+
+fn steps(vm: &Vm<DefaultPlatform>, code: &str) -> u64 {
+    let expr: Expr = yaml::from_str(code.trim()).unwrap();
+    let ctx = vm.context();
+    vm.eval_with(&CompiledExpr::new(&expr), &ctx).unwrap();
+    ctx.steps()
+}
+
+fn main() {
+    let fib: Expr = yaml::from_str(FIB.trim()).unwrap();
+    let fibs: Expr = yaml::from_str("{:rec: {fib15: [fib, {:: 15}]}}").unwrap();
+    let vm = Vm::new(DefaultPlatform)
+        .unwrap()
+        .with_env(vec![("fib".to_string(), fib), ("fibs".to_string(), fibs)]);
+
+    // Using a binding again doesn't evaluate it again.
+    let once = steps(&vm, ONCE);
+    let ten_times = steps(&vm, TEN_TIMES);
+    println!("{}", ten_times - once < 100);
+
+    // Neither does using a global binding again, or a field of it.
+    let first = steps(&vm, "fibs.fib15");
+    let again = steps(&vm, "fibs.fib15");
+    println!("{}", again < 10 && again < first);
+
+    // Only the fields of a record that are used are evaluated.
+    let field: Expr = yaml::from_str(FIELD.trim()).unwrap();
+    println!("{}", vm.eval(field).unwrap());
+}
@@ -32,6 +32,32 @@ const RESTORE: &str = "
   a
 ";
 
+const RECURSIVE: &str = "
+:let:
+  isEven:
+    :lambda: [n]
+    :do:
+      :if: {:==: [n, :: 0]}
+      :then: {:: true}
+      :else: [isOdd, {:-: [n, :: 1]}]
+  isOdd:
+    :lambda: [n]
+    :do:
+      :if: {:==: [n, :: 0]}
+      :then: {:: false}
+      :else: [isEven, {:-: [n, :: 1]}]
+:in:
+  [isEven, {:: 10}]
+";
+
+const CYCLIC: &str = "
+:let:
+  a: b
+  b: a
+:in:
+  a
+";
+
 fn main() {
     let vm = Vm::new(DefaultPlatform).unwrap();
 
@@ -43,4 +69,10 @@ fn main() {
 
     let restore: Expr = yaml::from_str(RESTORE.trim()).unwrap();
     println!("{}", vm.eval(restore).unwrap());
+
+    let recursive: Expr = yaml::from_str(RECURSIVE.trim()).unwrap();
+    println!("{}", vm.eval(recursive).unwrap());
+
+    let cyclic: Expr = yaml::from_str(CYCLIC.trim()).unwrap();
+    println!("{}", vm.eval(cyclic).unwrap_err());
 }
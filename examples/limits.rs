@@ -0,0 +1,62 @@
+use std::thread;
+use std::time::Duration;
+use yamlfun::{DefaultPlatform, Error, Loader, Vm};
+
+const LOOP: &str = "
+:let:
+  loop:
+    :lambda: [n]
+    :do: [loop, {:+: [n, :: 1]}]
+:in:
+  [loop, {:: 0}]
+";
+
+const RECURSE: &str = "
+:let:
+  recurse:
+    :lambda: [n]
+    :do: {:+: [:: 1, [recurse, n]]}
+:in:
+  [recurse, {:: 0}]
+";
+
+// Every level calls back from `List.map`.
+const MAP: &str = "
+:let:
+  recurse:
+    :lambda: [n]
+    :do: [List.map, recurse, {:: [1]}]
+:in:
+  [recurse, {:: 0}]
+";
+
+fn main() {
+    let vm = Vm::new(DefaultPlatform).unwrap();
+    let loop_ = Loader::new().load(LOOP.trim()).unwrap();
+    let recurse = Loader::new().load(RECURSE.trim()).unwrap();
+
+    let vm = vm.with_max_steps(10_000);
+    println!("{}", vm.eval(loop_.clone()).unwrap_err().root());
+
+    let vm = vm.with_max_depth(100);
+    println!("{}", vm.eval(recurse).unwrap_err().root());
+
+    // The most a thread with a 2MB stack, the default, allows.
+    let max_depth = if cfg!(debug_assertions) { 60 } else { 600 };
+    let map = Loader::new().load(MAP.trim()).unwrap();
+    let err = thread::Builder::new()
+        .stack_size(2 << 20)
+        .spawn(move || {
+            let vm = Vm::new(DefaultPlatform).unwrap().with_max_depth(max_depth);
+            vm.eval(map).unwrap_err()
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+    println!("{}", matches!(err.root(), Error::DepthLimitExceeded(_)));
+
+    let vm = vm
+        .with_max_steps(u64::MAX)
+        .with_timeout(Duration::from_millis(100));
+    println!("{}", vm.eval(loop_).unwrap_err().root());
+}
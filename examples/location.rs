@@ -0,0 +1,54 @@
+use yamlfun::{DefaultPlatform, Loader, Vm};
+
+const CODE: &str = "
+:let:
+  handle:
+    :lambda: [var]
+    :do:
+      :case: var
+      :of:
+        :int:
+          :as: n
+          :do: n
+:in: [handle, { :: foo }]
+";
+
+// `f` is called by `Maybe.map`, after a few calls in tail position.
+const TRACE: &str = "
+:let:
+  f:
+    :lambda: [x]
+    :do: {:raise: {:: boom}}
+  g:
+    :lambda: [n]
+    :do: [Maybe.withDefault, {:: 0}, [Maybe.map, f, [Maybe.just, n]]]
+:in: [g, {:: 1}]
+";
+
+fn main() {
+    let vm = Vm::new(DefaultPlatform).unwrap();
+
+    let expr = Loader::new()
+        .with_file("handle.yml")
+        .load(CODE.trim())
+        .unwrap();
+    let err = vm.eval(expr).unwrap_err();
+    println!("{}", err);
+    println!("{}", err.location().unwrap());
+    for frame in err.trace() {
+        println!("  in {}", frame);
+    }
+
+    let expr = Loader::new()
+        .with_file("trace.yml")
+        .load(TRACE.trim())
+        .unwrap();
+    let err = vm.eval(expr).unwrap_err();
+    println!("{}", err);
+    for frame in err.trace() {
+        match frame.elided {
+            0 => println!("  in {}", frame),
+            _ => println!("  ... {}", frame),
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use yamlfun::{CompiledExpr, DefaultPlatform, Loader, Vm};
+
+/// Counts the bytes allocated and not freed yet.
+struct Counting;
+
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        LIVE.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOC: Counting = Counting;
+
+// `f` is a function referring to the scope it's bound in.
+const PARTIAL: &str = "
+:let:
+  big: [List.range, {:: 0}, {:: 2000}]
+  mk:
+    :lambda: [x, y]
+    :do: {:+: [x, y, [List.length, big]]}
+  f: [mk, {:: 1}]
+:in:
+  [f, {:: 2}]
+";
+
+/// How many bytes `f` leaves behind when called `n` times, after calling it
+/// a few times to warm up.
+fn growth<F: FnMut()>(n: usize, mut f: F) -> usize {
+    (0..10).for_each(|_| f());
+    let before = LIVE.load(Ordering::Relaxed);
+    (0..n).for_each(|_| f());
+    LIVE.load(Ordering::Relaxed).saturating_sub(before)
+}
+
+fn main() {
+    let vm = Vm::new(DefaultPlatform).unwrap();
+    let expr = Loader::new().load(PARTIAL.trim()).unwrap();
+    let expr = CompiledExpr::new(&expr);
+    println!("{}", vm.eval_compiled(&expr).unwrap());
+
+    // Values that refer to their own scope are freed after the evaluation.
+    let bytes = growth(1_000, || {
+        vm.eval_compiled(&expr).unwrap();
+    });
+    println!("{}", bytes < 100_000);
+
+    // And the modules a VM loaded are freed with it.
+    let list = Loader::new().load("[List.length, {:: [1, 2]}]").unwrap();
+    let bytes = growth(300, || {
+        let vm = Vm::new(DefaultPlatform).unwrap();
+        vm.eval(list.clone()).unwrap();
+    });
+    println!("{}", bytes < 100_000);
+}
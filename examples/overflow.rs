@@ -0,0 +1,19 @@
+use yamlfun::vm::Overflow;
+use yamlfun::{yaml, DefaultPlatform, Expr, Vm};
+
+const ADD: &str = ":+: [:: 18446744073709551615, :: 1]";
+const MUL: &str = ":*: [:: -9223372036854775808, :: 2]";
+
+fn main() {
+    let vm = Vm::new(DefaultPlatform).unwrap();
+
+    let add: Expr = yaml::from_str(ADD.trim()).unwrap();
+    println!("{}", vm.eval(add.clone()).unwrap());
+
+    let mul: Expr = yaml::from_str(MUL.trim()).unwrap();
+    println!("{}", vm.eval(mul.clone()).unwrap());
+
+    let vm = vm.with_overflow(Overflow::Error);
+    println!("{}", vm.eval(add).unwrap_err());
+    println!("{}", vm.eval(mul).unwrap_err());
+}
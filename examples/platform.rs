@@ -1,11 +1,26 @@
 use yamlfun::platform::{DefaultPlatform, Platform};
-use yamlfun::{vm, yaml, Error, Expr, Function, Result, Value, Vm, Env};
+use yamlfun::{vm, yaml, Env, Error, Expr, Loader, Result, Runtime, Value, Vm};
 
 const PCALL: &str = r#"
 :lambda: [path]
 :do:
-  - :platform: import
-    :arg: path
+  :platform: import
+  :arg: path
+"#;
+
+const RETRY: &str = r#"
+:lambda: [attempt]
+:do:
+  :platform: retry
+  :arg: attempt
+"#;
+
+const FLAKY: &str = r#"
+:lambda: [n]
+:do:
+  :if: {:<: [n, :: 3]}
+  :then: {:raise: {:fmt: "attempt ${n} failed"}}
+  :else: {:fmt: "attempt ${n} worked"}
 "#;
 
 struct MyPlatform(DefaultPlatform);
@@ -16,31 +31,60 @@ impl Platform for MyPlatform {
 
         let import = yaml::from_str(PCALL)?;
         state.set_env("import".into(), import);
+
+        let retry = yaml::from_str(RETRY)?;
+        state.set_env("retry".into(), retry);
         Ok(())
     }
 
-    fn call(&self, env: Env, name: &str, arg: Value) -> Result<Value> {
+    fn call(&self, env: Env, name: &str, arg: Value, rt: &dyn Runtime) -> Result<Value> {
         match name {
             "import" => match arg {
                 Value::String(s) => {
                     let yml = std::fs::read_to_string(s)
                         .map_err(|e| Error::PlatformCallError(e.to_string()))?;
                     let expr: Expr = yaml::from_str(&yml)?;
-                    let func = Function::new(env, vec![], expr);
-                    Ok(Value::Function(func.into()))
+                    rt.eval(&expr, env)
                 }
                 v => Err(Error::InvalidArguments(name.into(), vec![v])),
             },
-            _ => self.0.call(env, name, arg),
+
+            // Call the function with the number of the attempt, until it
+            // doesn't raise anything.
+            "retry" => match arg {
+                Value::Function(attempt) => {
+                    let mut n: i64 = 1;
+                    loop {
+                        match rt.call(&attempt, vec![Value::from(n)]) {
+                            // Errors come back with where they happened.
+                            Err(e) if matches!(e.root(), Error::Raised(_)) && n < 5 => n += 1,
+                            res => return res,
+                        }
+                    }
+                }
+                v => Err(Error::InvalidArguments(name.into(), vec![v])),
+            },
+
+            _ => self.0.call(env, name, arg, rt),
         }
     }
+
+    fn names(&self) -> Vec<String> {
+        vec!["import".into(), "retry".into()]
+    }
 }
 
 fn main() {
     let platform = MyPlatform(DefaultPlatform);
-    let vm = Vm::new(platform).unwrap();
+    let flaky = Loader::new().with_file("flaky.yml").load(FLAKY).unwrap();
+    let vm = Vm::new(platform)
+        .unwrap()
+        .with_env(vec![("flaky".to_string(), flaky)]);
 
     let rec: Expr = yaml::from_str("[import, {:: ./concept.yml}]").unwrap();
     let rec = vm.eval(rec).unwrap();
     println!("{}", &rec);
+
+    let res: Expr = yaml::from_str("[retry, flaky]").unwrap();
+    println!("{}", vm.eval(res).unwrap());
 }
@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+use yamlfun::platform::Platform;
+use yamlfun::{vm, Env, Error, Loader, PlatformStack, Result, Runtime, Value, Vm};
+
+const SCRIPT: &str = "
+:list:
+  - :platform: env.get
+    :arg: { :: USER }
+  - :platform: log.info
+    :arg: { :: starting }
+  - :platform: log.debug
+    :arg: { :: connecting }
+  - :platform: env.keys
+    :arg: { :: null }
+";
+
+/// Variables of a fake environment.
+struct EnvPlatform(HashMap<String, String>);
+
+impl Platform for EnvPlatform {
+    fn init(&self, _: &mut vm::State) -> Result<()> {
+        Ok(())
+    }
+
+    fn call(&self, _: Env, name: &str, arg: Value, _: &dyn Runtime) -> Result<Value> {
+        match (name, arg) {
+            ("get", Value::String(key)) => Ok(self
+                .0
+                .get(&key)
+                .cloned()
+                .map(Value::from)
+                .unwrap_or_default()),
+            ("keys", _) => {
+                let mut keys: Vec<&String> = self.0.keys().collect();
+                keys.sort();
+                Ok(keys
+                    .into_iter()
+                    .cloned()
+                    .map(Value::from)
+                    .collect::<Vec<_>>()
+                    .into())
+            }
+            (_, arg) => Err(Error::InvalidArguments(name.into(), vec![arg])),
+        }
+    }
+
+    fn names(&self) -> Vec<String> {
+        vec!["get".into(), "keys".into()]
+    }
+}
+
+/// Handles every name under its namespace, since it doesn't list any.
+struct LogPlatform;
+
+impl Platform for LogPlatform {
+    fn init(&self, _: &mut vm::State) -> Result<()> {
+        Ok(())
+    }
+
+    fn call(&self, _: Env, name: &str, arg: Value, _: &dyn Runtime) -> Result<Value> {
+        println!("[{}] {}", name, arg);
+        Ok(Value::Null)
+    }
+}
+
+fn main() {
+    let vars = vec![("USER".to_string(), "alice".to_string())];
+    let platform = PlatformStack::new()
+        .with("env", EnvPlatform(vars.into_iter().collect()))
+        .with("log", LogPlatform);
+
+    let vm = Vm::new(platform).unwrap();
+    println!("{:?}", vm.platform().names());
+
+    let script = Loader::new().load(SCRIPT.trim()).unwrap();
+    println!("{}", vm.eval(script).unwrap());
+
+    let script = Loader::new()
+        .load("{:platform: fs.read, :arg: {:: /etc/passwd}}")
+        .unwrap();
+    println!("{}", vm.eval(script).unwrap_err().root());
+}
@@ -0,0 +1,37 @@
+use yamlfun::{DefaultPlatform, Loader, Prelude, Vm};
+
+const GREETINGS: &str = "
+:rec:
+  greet:
+    :lambda: [name]
+    :do:
+      :++: [{:: 'Hello, '}, name]
+";
+
+const SCRIPT: &str = "
+- greet
+- - Maybe.withDefault
+  - {:: nobody}
+  - [List.head, {:: [world]}]
+";
+
+fn main() {
+    let script = Loader::new().load(SCRIPT.trim()).unwrap();
+
+    // Nothing in scope.
+    let vm = Vm::with_prelude(DefaultPlatform, Prelude::new()).unwrap();
+    println!("{}", vm.eval(script.clone()).unwrap_err().root());
+
+    // Only what the script needs, from the standard library and our own
+    // module.
+    let prelude = Prelude::new()
+        .with("Maybe", "Maybe")
+        .with("List", "List")
+        .with("greet", "Greetings.greet");
+
+    let vm = Vm::with_prelude(DefaultPlatform, prelude)
+        .unwrap()
+        .with_module("Greetings", GREETINGS.trim());
+
+    println!("{}", vm.eval(script).unwrap());
+}
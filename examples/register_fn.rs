@@ -0,0 +1,43 @@
+use yamlfun::{DefaultPlatform, Loader, Result, Value, Vm};
+
+const SCRIPT: &str = "
+:let:
+  get: [Http.get, { :: example.com }]
+:in:
+  :list:
+    - [get, { :: 80 }]
+    - [Http.status, [get, { :: 443 }]]
+    - [sum, { :: [1, 2, 3.5] }]
+    - [List.product, [List.range, { :: 1 }, { :: 4 }]]
+";
+
+fn main() {
+    let mut vm = Vm::new(DefaultPlatform).unwrap();
+
+    vm.register_fn("Http.get", |host: String, port: i64| -> Result<Value> {
+        Ok(format!("GET http://{}:{}/", host, port).into())
+    })
+    .unwrap();
+    vm.register_fn("Http.status", |_: String| Ok(true)).unwrap();
+    vm.register_fn("sum", |nums: Vec<f64>| Ok(nums.iter().sum::<f64>()))
+        .unwrap();
+
+    // Added next to the functions of the module.
+    vm.register_fn("List.product", |nums: Vec<f64>| {
+        Ok(nums.iter().product::<f64>())
+    })
+    .unwrap();
+
+    let script = Loader::new().load(SCRIPT.trim()).unwrap();
+    println!("{}", vm.eval(script).unwrap());
+
+    // Arguments are checked when all of them are there.
+    let script = Loader::new()
+        .load("[Http.get, { :: 80 }, { :: 80 }]")
+        .unwrap();
+    println!("{}", vm.eval(script).unwrap_err().root());
+
+    // Only records can have functions added to them.
+    let err = vm.register_fn("add.twice", |n: i64| Ok(n * 2)).unwrap_err();
+    println!("{}", err);
+}
@@ -35,7 +35,7 @@ wtf:
 "#;
 
 fn main() {
-    let val: yamlfun::Value = yamlfun::yaml::from_str(&VAL).unwrap();
+    let val: yamlfun::Value = yamlfun::yaml::from_str(VAL).unwrap();
     println!("{}", &val);
 
     let val = serde_yaml::to_string(&val).unwrap();
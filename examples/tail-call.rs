@@ -0,0 +1,47 @@
+use yamlfun::{DefaultPlatform, Loader, Vm};
+
+const EVEN_ODD: &str = "
+:let:
+  isEven:
+    :lambda: [n]
+    :do:
+      :if: {:==: [n, :: 0]}
+      :then: {:: true}
+      :else: [isOdd, {:-: [n, :: 1]}]
+  isOdd:
+    :lambda: [n]
+    :do:
+      :if: {:==: [n, :: 0]}
+      :then: {:: false}
+      :else: [isEven, {:-: [n, :: 1]}]
+:in:
+  [isEven, {:: 10001}]
+";
+
+const FOLDL: &str = "
+:let:
+  foldl:
+    :lambda: [f, acc, list]
+    :do:
+      :case: list
+      :of:
+        :==:
+          []: acc
+        :list:
+          :as: [head, tail]
+          :do: [foldl, f, [f, acc, head], tail]
+:in:
+  [foldl, (+), {:: 0}, {:: LIST}]
+";
+
+fn main() {
+    let vm = Vm::new(DefaultPlatform).unwrap();
+
+    let even_odd = Loader::new().load(EVEN_ODD.trim()).unwrap();
+    println!("{}", vm.eval(even_odd).unwrap());
+
+    let list: Vec<String> = (1..=2000).map(|i| i.to_string()).collect();
+    let foldl = FOLDL.replace("LIST", &format!("[{}]", list.join(", ")));
+    let foldl = Loader::new().load(foldl.trim()).unwrap();
+    println!("{}", vm.eval(foldl).unwrap());
+}
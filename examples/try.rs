@@ -0,0 +1,41 @@
+use yamlfun::{DefaultPlatform, Expr, Loader, Vm};
+
+fn load(code: &str) -> Expr {
+    Loader::new().load(code.trim()).unwrap()
+}
+
+const SAFE_DIV: &str = "
+:lambda: [x, y]
+:do:
+  :try: { :/: [x, y] }
+  :catch:
+    :lambda: [error]
+    :do: { :raise: { :rec: { reason: error, x: x } } }
+";
+
+const LOOP: &str = "
+:let:
+  loop:
+    :lambda: [n]
+    :do: [loop, n]
+:in:
+  :try: [loop, { :: 0 }]
+  :catch:
+    :lambda: [error]
+    :do: { :: caught }
+";
+
+fn main() {
+    let vm = Vm::new(DefaultPlatform)
+        .unwrap()
+        .with_env(vec![("safeDiv".to_string(), load(SAFE_DIV))]);
+
+    println!("{}", vm.eval(load("[safeDiv, :: 10, :: 4]")).unwrap());
+
+    let err = vm.eval(load("[safeDiv, :: 10, :: 0]")).unwrap_err();
+    println!("{}", err.root());
+
+    // Going over a limit can't be caught.
+    let vm = vm.with_max_steps(1000);
+    println!("{}", vm.eval(load(LOOP)).unwrap_err().root());
+}
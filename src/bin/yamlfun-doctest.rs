@@ -3,7 +3,7 @@ use serde::{Deserialize, Serialize};
 use serde_json as json;
 use std::fs;
 use std::io::{self, Read};
-use yamlfun::{yaml, DefaultPlatform, Expr, Value, Vm};
+use yamlfun::{yaml, DefaultPlatform, Expr, Loader, Value, Vm};
 
 #[derive(Debug, Deserialize, Serialize)]
 struct Test {
@@ -20,17 +20,17 @@ struct Test {
 fn main() -> Result<()> {
     let mut vm = Vm::new(DefaultPlatform)?;
 
-    let code = if let Some(file) = std::env::args().skip(1).next() {
-        fs::read_to_string(file)?
+    let (code, loader) = if let Some(file) = std::env::args().nth(1) {
+        (fs::read_to_string(&file)?, Loader::new().with_file(file))
     } else {
         let stdin = io::stdin();
         let mut stdin = stdin.lock();
         let mut code = String::new();
         stdin.read_to_string(&mut code)?;
-        code
+        (code, Loader::new().with_file("<stdin>"))
     };
 
-    let env: Value = vm.eval(yaml::from_str(&code)?)?;
+    let env: Value = vm.eval(loader.load(&code)?)?;
     if let Value::Record(rec) = env {
         vm = vm.with_env(rec.iter().map(|(k, v)| (k.to_string(), v.clone().into())));
     };
@@ -43,7 +43,12 @@ fn main() -> Result<()> {
         .collect::<Vec<String>>()
         .join("\n");
 
-    let tests: Vec<Test> = yaml::from_str(&tests)?;
+    // A file without tests has nothing to parse.
+    let tests: Vec<Test> = if tests.trim().is_empty() {
+        vec![]
+    } else {
+        yaml::from_str(&tests)?
+    };
 
     let mut results = vec![];
     for (i, test) in tests.into_iter().enumerate() {
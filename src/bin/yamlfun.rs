@@ -1,23 +1,36 @@
 use anyhow::Result;
 use std::fs;
 use std::io::{self, Read};
-use yamlfun::{yaml, DefaultPlatform, Expr, Vm};
+use yamlfun::{DefaultPlatform, Loader, Vm};
 
 fn main() -> Result<()> {
     let vm = Vm::new(DefaultPlatform)?;
 
-    let code = if let Some(file) = std::env::args().skip(1).next() {
-        fs::read_to_string(file)?
+    let (code, loader) = if let Some(file) = std::env::args().nth(1) {
+        (fs::read_to_string(&file)?, Loader::new().with_file(file))
     } else {
         let stdin = io::stdin();
         let mut stdin = stdin.lock();
         let mut code = String::new();
         stdin.read_to_string(&mut code)?;
-        code
+        (code, Loader::new().with_file("<stdin>"))
     };
 
-    let expr: Expr = yaml::from_str(&code)?;
-    let res = vm.eval(expr).expect("failed to run");
-    println!("{}", res);
-    Ok(())
+    let expr = loader.load(&code)?;
+    match vm.eval(expr) {
+        Ok(res) => {
+            println!("{}", res);
+            Ok(())
+        }
+        Err(err) => {
+            eprintln!("error: {}", err);
+            for frame in err.trace() {
+                match frame.elided {
+                    0 => eprintln!("  in {}", frame),
+                    _ => eprintln!("  ... {}", frame),
+                }
+            }
+            std::process::exit(1);
+        }
+    }
 }
@@ -0,0 +1,941 @@
+use crate::env::{Thunk, WeakEnv};
+use crate::expr::Expr;
+use crate::loader::{Location, Origin};
+use crate::native::Native;
+use crate::platform::Platform;
+use crate::value::{Function, Record as RecordVal};
+use crate::vm::{Context, Overflow};
+use crate::{yaml, Env, Number, Value, Yaml};
+use crate::{Error, Frame, Result};
+use indexmap::{IndexMap, IndexSet};
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+use std::sync::{Arc, Weak};
+
+/// An expression compiled ahead of time, so that it can be evaluated many
+/// times without walking or copying the expression tree again.
+///
+/// Names bound by `:lambda`, `:let` and `:case` are resolved to slots. Other
+/// names are looked up in the VM's environment when they are evaluated.
+#[derive(Debug, Clone)]
+pub struct CompiledExpr(Arc<Code>);
+
+impl CompiledExpr {
+    pub fn new(expr: &Expr) -> Self {
+        Self(compile(expr))
+    }
+
+    pub fn eval<P: Platform>(&self, env: Env, ctx: &Context<P>) -> Result<Value> {
+        eval(self.0.clone(), Scope::new(env), ctx)
+    }
+}
+
+impl From<Expr> for CompiledExpr {
+    fn from(expr: Expr) -> Self {
+        Self::new(&expr)
+    }
+}
+
+pub(crate) fn compile(expr: &Expr) -> Arc<Code> {
+    expr.compile(&mut Scopes::default())
+}
+
+/// Compile the value of a binding, naming the function it makes, if any.
+pub(crate) fn compile_named(name: &str, expr: &Expr) -> Arc<Code> {
+    expr.compile_named(name, &mut Scopes::default())
+}
+
+#[derive(Debug)]
+pub(crate) enum Code {
+    Value(Value),
+    /// A name bound in an enclosing scope: how many scopes up, and where.
+    Local(usize, usize),
+    /// A name from the environment.
+    Global(String),
+    /// A name that might be bound by a `:with`, so it's looked up at runtime.
+    Dynamic(String),
+    /// A module from the VM's registry.
+    Module(String),
+    Field(Arc<Code>, String),
+    Lambda(Arc<Lambda>),
+    Call(Vec<Arc<Code>>, Option<String>),
+    IfElse(Arc<Code>, Arc<Code>, Arc<Code>),
+    LetIn(Arc<[String]>, Vec<Arc<Code>>, Arc<Code>),
+    Arith(&'static Arith, Vec<Arc<Code>>),
+    Append(Vec<Arc<Code>>),
+    Equals(Vec<Arc<Code>>),
+    Compare(&'static str, fn(Ordering) -> bool, Vec<Arc<Code>>),
+    And(Vec<Arc<Code>>),
+    Or(Vec<Arc<Code>>),
+    List(Vec<Arc<Code>>),
+    Record(Vec<(String, Arc<Code>)>),
+    With(Vec<Arc<Code>>, Arc<Code>),
+    Update(Arc<Code>, Vec<(String, Arc<Code>)>, IndexSet<String>),
+    /// A platform call, and the file making it.
+    PlatformCall(String, Arc<Code>, Option<Arc<Origin>>),
+    /// A module, and the file importing it.
+    Import(String, Option<Arc<Origin>>),
+    Fmt(Vec<Piece>),
+    /// A native function, by name.
+    Native(String),
+    /// The body of a native function, getting the function's arguments.
+    NativeBody(Native),
+    Raise(Arc<Code>),
+    Try(Arc<Code>, Arc<Code>),
+    Chain(Vec<Link>),
+    CaseOf(Arc<Code>, Box<Cases>),
+    Located(Arc<Code>, Location),
+}
+
+impl Code {
+    /// The function it makes, if that's all it does.
+    pub(crate) fn as_lambda(&self) -> Option<&Arc<Lambda>> {
+        match self {
+            Self::Lambda(lambda) => Some(lambda),
+            Self::Located(code, _) => code.as_lambda(),
+            _ => None,
+        }
+    }
+
+    /// The fields of the record it makes, if that's all it does.
+    pub(crate) fn as_record(&self) -> Option<&[(String, Arc<Code>)]> {
+        match self {
+            Self::Record(items) => Some(items),
+            Self::Located(code, _) => code.as_record(),
+            _ => None,
+        }
+    }
+}
+
+/// A part of a `:fmt` template.
+#[derive(Debug)]
+pub(crate) enum Piece {
+    Text(String),
+    Value(Arc<Code>),
+}
+
+#[derive(Debug)]
+pub(crate) struct Lambda {
+    pub(crate) params: Arc<[String]>,
+    pub(crate) body: Arc<Code>,
+    /// The name it's bound to, to refer to it in a stack trace.
+    pub(crate) name: Option<String>,
+}
+
+/// A function in a `:chain`, and how to refer to it in a stack trace.
+#[derive(Debug)]
+pub(crate) struct Link {
+    pub(crate) code: Arc<Code>,
+    pub(crate) name: Option<String>,
+    pub(crate) location: Option<Location>,
+}
+
+/// The compiled arms of a `:case`.
+#[derive(Debug, Default)]
+pub(crate) struct Cases {
+    pub(crate) exact: IndexMap<Yaml, Arc<Code>>,
+    pub(crate) integer: Option<Arm>,
+    pub(crate) float: Option<Arm>,
+    pub(crate) string: Option<Arm>,
+    pub(crate) function: Option<Arm>,
+    pub(crate) list: Option<Arm>,
+    /// The field paths to extract, bound to the names of the arm.
+    pub(crate) record: Option<(Vec<Arc<Code>>, Arm)>,
+    pub(crate) default: Option<Arm>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Arm {
+    pub(crate) names: Arc<[String]>,
+    pub(crate) body: Arc<Code>,
+}
+
+/// The names bound at each level of nesting, while compiling. A `None`
+/// level is a `:with`, which binds names only known at runtime.
+#[derive(Default)]
+pub(crate) struct Scopes(Vec<Option<Arc<[String]>>>);
+
+impl Scopes {
+    pub(crate) fn resolve(&self, name: &str) -> Code {
+        for (depth, names) in self.0.iter().rev().enumerate() {
+            match names {
+                Some(names) => {
+                    if let Some(index) = names.iter().rposition(|n| n == name) {
+                        return Code::Local(depth, index);
+                    }
+                }
+                None => return Code::Dynamic(name.into()),
+            }
+        }
+        Code::Global(name.into())
+    }
+
+    /// Compile with the names bound in a nested scope.
+    pub(crate) fn nested<T>(
+        &mut self,
+        names: Option<Arc<[String]>>,
+        f: impl FnOnce(&mut Self) -> T,
+    ) -> T {
+        self.0.push(names);
+        let res = f(self);
+        self.0.pop();
+        res
+    }
+}
+
+/// The names in scope while evaluating: the environment, and the values
+/// bound to the slots of the enclosing scopes.
+#[derive(Clone, Default)]
+pub(crate) struct Scope {
+    pub(crate) env: Env,
+    locals: Option<Arc<Locals>>,
+}
+
+struct Locals {
+    names: Arc<[String]>,
+    values: Vec<Local>,
+    parent: Option<Arc<Locals>>,
+}
+
+#[derive(Clone)]
+enum Local {
+    Value(Value),
+    Thunk(Thunk),
+}
+
+impl Local {
+    fn get<P: Platform>(&self, ctx: &Context<P>) -> Result<Value> {
+        match self {
+            Self::Value(v) => Ok(v.clone()),
+            Self::Thunk(t) => t.force(ctx),
+        }
+    }
+}
+
+impl Scope {
+    pub(crate) fn new(env: Env) -> Self {
+        Self { env, locals: None }
+    }
+
+    pub(crate) fn downgrade(&self) -> WeakScope {
+        WeakScope {
+            env: self.env.downgrade(),
+            locals: self.locals.as_ref().map(Arc::downgrade),
+        }
+    }
+
+    /// A scope with the arguments of a function call bound.
+    pub(crate) fn with_args(&self, names: Arc<[String]>, args: Vec<Value>) -> Self {
+        self.push(names, args.into_iter().map(Local::Value).collect())
+    }
+
+    fn push(&self, names: Arc<[String]>, values: Vec<Local>) -> Self {
+        Self {
+            env: self.env.clone(),
+            locals: Some(Arc::new(Locals {
+                names,
+                values,
+                parent: self.locals.clone(),
+            })),
+        }
+    }
+
+    /// The values bound in the innermost scope, e.g. a function's arguments.
+    fn args<P: Platform>(&self, ctx: &Context<P>) -> Result<Vec<Value>> {
+        self.locals
+            .iter()
+            .flat_map(|l| l.values.iter())
+            .map(|v| v.get(ctx))
+            .collect()
+    }
+
+    fn levels(&self) -> impl Iterator<Item = &Locals> {
+        std::iter::successors(self.locals.as_deref(), |l| l.parent.as_deref())
+    }
+
+    fn local(&self, depth: usize, index: usize) -> Option<&Local> {
+        self.levels().nth(depth).and_then(|l| l.values.get(index))
+    }
+
+    /// The binding a name refers to, if it's not evaluated yet.
+    fn thunk(&self, code: &Code) -> Option<Thunk> {
+        match code {
+            Code::Local(depth, index) => match self.local(*depth, *index)? {
+                Local::Thunk(thunk) => Some(thunk.clone()),
+                Local::Value(_) => None,
+            },
+            Code::Global(name) => self.env.get_thunk(name),
+            Code::Dynamic(name) => match self.lookup(name) {
+                Some(Local::Thunk(thunk)) => Some(thunk.clone()),
+                Some(Local::Value(_)) => None,
+                None => self.env.get_thunk(name),
+            },
+            _ => None,
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Local> {
+        self.levels().find_map(|l| {
+            l.names
+                .iter()
+                .rposition(|n| n == name)
+                .and_then(|i| l.values.get(i))
+        })
+    }
+}
+
+/// A `Scope` that doesn't keep its names alive.
+#[derive(Clone, Default)]
+pub(crate) struct WeakScope {
+    env: WeakEnv,
+    locals: Option<Weak<Locals>>,
+}
+
+impl WeakScope {
+    pub(crate) fn upgrade(&self) -> Option<Scope> {
+        let locals = match &self.locals {
+            Some(locals) => Some(locals.upgrade()?),
+            None => None,
+        };
+        Some(Scope {
+            env: self.env.upgrade()?,
+            locals,
+        })
+    }
+}
+
+impl PartialEq for Scope {
+    fn eq(&self, other: &Self) -> bool {
+        let same_locals = match (&self.locals, &other.locals) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        };
+        same_locals && self.env == other.env
+    }
+}
+
+impl fmt::Debug for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<&String> = self.levels().flat_map(|l| l.names.iter()).collect();
+        f.debug_struct("Scope")
+            .field("env", &self.env)
+            .field("locals", &names)
+            .finish()
+    }
+}
+
+/// What's left to do after an evaluation step.
+pub(crate) enum Tail {
+    Value(Value),
+    Eval(Arc<Code>, Scope),
+    Call(Box<Function>, Frame),
+}
+
+pub(crate) fn eval<P: Platform>(code: Arc<Code>, scope: Scope, ctx: &Context<P>) -> Result<Value> {
+    run(Tail::Eval(code, scope), ctx)
+}
+
+fn eval_in<P: Platform>(code: &Arc<Code>, scope: &Scope, ctx: &Context<P>) -> Result<Value> {
+    eval(code.clone(), scope.clone(), ctx)
+}
+
+/// Evaluate in a loop, so that expressions and function calls in tail
+/// position don't grow the Rust stack.
+///
+/// Only the first and the latest tail calls are kept in the stack trace of an
+/// error, with the number of those in between.
+pub(crate) fn run<P: Platform>(next: Tail, ctx: &Context<P>) -> Result<Value> {
+    ctx.enter()?;
+    let res = run_loop(next, ctx);
+    ctx.leave();
+    res
+}
+
+fn run_loop<P: Platform>(mut next: Tail, ctx: &Context<P>) -> Result<Value> {
+    let mut location = None;
+    let mut first = None;
+    let mut frame = None;
+    let mut elided = 0;
+
+    loop {
+        let res = match next {
+            Tail::Value(v) => return Ok(v),
+
+            Tail::Eval(code, scope) => match &*code {
+                Code::Located(inner, loc) => {
+                    location = Some(loc.clone());
+                    match &**inner {
+                        Code::Call(call, name) => {
+                            eval_call(call, name, &scope, ctx, location.as_ref())
+                        }
+                        _ => Ok(Tail::Eval(inner.clone(), scope)),
+                    }
+                }
+                code => step(code, scope, ctx),
+            },
+
+            Tail::Call(f, fr) => {
+                let args = fr.args.clone();
+                if first.is_none() {
+                    first = Some(fr);
+                } else if frame.replace(fr).is_some() {
+                    elided += 1;
+                }
+                f.enter(args)
+            }
+        };
+
+        next = match res.and_then(|tail| ctx.step().map(|()| tail)) {
+            Ok(tail) => tail,
+            Err(mut err) => {
+                if let Some(location) = location {
+                    err = err.at(location);
+                }
+                if let Some(frame) = frame {
+                    err = err.with_frame(frame);
+                }
+                if elided > 0 {
+                    err = err.with_frame(Frame::elided(elided));
+                }
+                if let Some(first) = first {
+                    err = err.with_frame(first);
+                }
+                return Err(err);
+            }
+        };
+    }
+}
+
+fn eval_call<P: Platform>(
+    call: &[Arc<Code>],
+    name: &Option<String>,
+    scope: &Scope,
+    ctx: &Context<P>,
+    location: Option<&Location>,
+) -> Result<Tail> {
+    if let Some((func, args)) = call.split_first() {
+        let val = eval_in(func, scope, ctx)?;
+        if let Value::Function(f) = val {
+            let args = args
+                .iter()
+                .map(|a| eval_in(a, scope, ctx))
+                .collect::<Result<Vec<Value>>>()?;
+            let name = f.name().map(String::from).or_else(|| name.clone());
+            Ok(Tail::Call(f, Frame::new(name, args, location)))
+        } else {
+            Err(Error::NotAFunction(val))
+        }
+    } else {
+        Err(Error::NoFunction)
+    }
+}
+
+/// Evaluate the code until it's either done, or the rest of the work is a
+/// tail position expression or function call.
+fn step<P: Platform>(code: &Code, scope: Scope, ctx: &Context<P>) -> Result<Tail> {
+    match code {
+        Code::Value(v) => Ok(Tail::Value(v.clone())),
+
+        Code::Located(code, location) => eval_in(code, &scope, ctx)
+            .map(Tail::Value)
+            .map_err(|e| e.at(location.clone())),
+
+        Code::Local(depth, index) => match scope.local(*depth, *index) {
+            Some(local) => local.get(ctx).map(Tail::Value),
+            None => Err(Error::Undefined(format!("<slot {}.{}>", depth, index))),
+        },
+
+        Code::Global(name) => match scope.env.get_thunk(name) {
+            Some(thunk) => thunk.force(ctx).map(Tail::Value),
+            None => Err(Error::Undefined(name.clone())),
+        },
+
+        Code::Module(name) => ctx.modules.load(name, ctx).map(Tail::Value),
+
+        Code::Dynamic(name) => match scope.lookup(name) {
+            Some(local) => local.get(ctx).map(Tail::Value),
+            None => step(&Code::Global(name.clone()), scope, ctx),
+        },
+
+        Code::Field(code, fields) => {
+            if let Some(thunk) = scope.thunk(code) {
+                return thunk.force_field(fields, ctx).map(Tail::Value);
+            }
+            let val = eval_in(code, &scope, ctx)?;
+            val.get_from_yaml_nested(fields.split('.').map(RecordVal::de_field_name))
+                .cloned()
+                .map(Tail::Value)
+        }
+
+        Code::List(items) => {
+            let mut list = vec![];
+            for i in items {
+                list.push(eval_in(i, &scope, ctx)?);
+            }
+            Ok(Tail::Value(Value::List(list.into())))
+        }
+
+        Code::Record(items) => {
+            let mut rec = IndexMap::new();
+            for (k, v) in items {
+                rec.insert(k.clone(), eval_in(v, &scope, ctx)?);
+            }
+            Ok(Tail::Value(Value::Record(rec.into())))
+        }
+
+        Code::Lambda(l) => Ok(Tail::Value(Function::from_lambda(l.clone(), scope).into())),
+
+        Code::IfElse(if_, then, else_) => match eval_in(if_, &scope, ctx)? {
+            Value::Bool(true) => Ok(Tail::Eval(then.clone(), scope)),
+            Value::Bool(false) => Ok(Tail::Eval(else_.clone(), scope)),
+            v => Err(Error::NotABoolean(v)),
+        },
+
+        Code::LetIn(names, values, body) => {
+            let thunks: Vec<Thunk> = names
+                .iter()
+                .zip(values)
+                .map(|(k, v)| Thunk::new(k.clone(), v.clone()))
+                .collect();
+            let scope = scope.push(
+                names.clone(),
+                thunks.iter().cloned().map(Local::Thunk).collect(),
+            );
+
+            // Every binding sees all the others, including itself.
+            for thunk in thunks {
+                thunk.bind_local(&scope);
+            }
+            Ok(Tail::Eval(body.clone(), scope))
+        }
+
+        Code::Call(call, name) => eval_call(call, name, &scope, ctx, None),
+
+        Code::Arith(op, args) => eval_arith(op, args, &scope, ctx).map(Tail::Value),
+
+        Code::Append(args) => {
+            let mut args = args.iter();
+            if let Some(sum) = args.next() {
+                let mut sum = eval_in(sum, &scope, ctx)?;
+                for arg in args {
+                    let arg = eval_in(arg, &scope, ctx)?;
+                    match (&sum, &arg) {
+                        (Value::List(l1), Value::List(l2)) => {
+                            let mut list = l1.0.clone();
+                            list.append(&mut l2.0.clone());
+                            sum = Value::List(list.into());
+                        }
+                        (Value::String(s1), Value::String(s2)) => {
+                            sum = Value::String(format!("{}{}", s1, s2));
+                        }
+                        (n1, n2) => {
+                            return Err(Error::InvalidArguments(
+                                "++".into(),
+                                vec![n1.clone(), n2.clone()],
+                            ))
+                        }
+                    }
+                }
+                Ok(Tail::Value(sum))
+            } else {
+                Err(Error::NotEnoughArguments("++".into(), 1, 0))
+            }
+        }
+
+        Code::Equals(args) => {
+            if args.len() != 2 {
+                Err(Error::NotEnoughArguments("+".into(), 2, args.len()))
+            } else {
+                let arg1 = eval_in(&args[0], &scope, ctx)?;
+                let arg2 = eval_in(&args[1], &scope, ctx)?;
+                Ok(Tail::Value(Yaml::Bool(arg1 == arg2).into()))
+            }
+        }
+
+        Code::Compare(name, test, args) => {
+            eval_compare(name, args, &scope, ctx, *test).map(Tail::Value)
+        }
+
+        Code::And(args) => {
+            for arg in args {
+                match eval_in(arg, &scope, ctx)? {
+                    Value::Bool(true) => {}
+                    Value::Bool(false) => return Ok(Tail::Value(Value::Bool(false))),
+                    v => return Err(Error::NotABoolean(v)),
+                }
+            }
+            Ok(Tail::Value(Value::Bool(true)))
+        }
+
+        Code::Or(args) => {
+            for arg in args {
+                match eval_in(arg, &scope, ctx)? {
+                    Value::Bool(true) => return Ok(Tail::Value(Value::Bool(true))),
+                    Value::Bool(false) => {}
+                    v => return Err(Error::NotABoolean(v)),
+                }
+            }
+            Ok(Tail::Value(Value::Bool(false)))
+        }
+
+        Code::With(with, body) => {
+            let mut names = vec![];
+            let mut values = vec![];
+            for rec in with {
+                match eval_in(rec, &scope, ctx)? {
+                    Value::Record(r) => {
+                        for (k, v) in r.iter() {
+                            names.push(k.clone());
+                            values.push(Local::Value(v.clone()));
+                        }
+                    }
+                    v => return Err(Error::NotARecord(v)),
+                }
+            }
+            Ok(Tail::Eval(body.clone(), scope.push(names.into(), values)))
+        }
+
+        Code::Update(update, set, unset) => {
+            let rec = eval_in(update, &scope, ctx)?;
+
+            match rec {
+                Value::Record(r) => {
+                    let mut newrec: IndexMap<String, Value> = r
+                        .iter()
+                        .filter(|(k, _)| !unset.contains(*k))
+                        .map(|(k, v)| (k.clone(), v.clone()))
+                        .collect();
+
+                    for (k, v) in set {
+                        newrec.insert(k.clone(), eval_in(v, &scope, ctx)?);
+                    }
+
+                    Ok(Tail::Value(newrec.into()))
+                }
+                _ => Err(Error::NotARecord(rec)),
+            }
+        }
+
+        Code::PlatformCall(name, arg, from) => {
+            ctx.allow(name, from.as_deref())?;
+            let arg = eval_in(arg, &scope, ctx)?;
+            if let Some(audit) = &ctx.config.audit {
+                audit.record(name, &arg);
+            }
+            ctx.platform
+                .call(scope.env, name, arg, ctx)
+                .map(Tail::Value)
+        }
+
+        Code::Import(path, from) => ctx
+            .modules
+            .import(path, from.as_deref(), ctx)
+            .map(Tail::Value),
+
+        Code::Fmt(pieces) => {
+            let mut string = String::new();
+            for piece in pieces {
+                match piece {
+                    Piece::Text(text) => string.push_str(text),
+                    Piece::Value(code) => match eval_in(code, &scope, ctx)? {
+                        Value::String(s) => string.push_str(&s),
+                        v => string.push_str(&v.to_string()),
+                    },
+                }
+            }
+            Ok(Tail::Value(string.into()))
+        }
+
+        Code::Native(name) => match ctx.natives.get(name) {
+            Some(f) => Ok(Tail::Value(f.clone().into())),
+            None => Err(Error::Undefined(name.clone())),
+        },
+
+        Code::NativeBody(native) => native.call(scope.args(ctx)?, ctx).map(Tail::Value),
+
+        Code::Raise(value) => Err(Error::Raised(eval_in(value, &scope, ctx)?)),
+
+        Code::Try(body, catch) => match eval_in(body, &scope, ctx) {
+            Ok(v) => Ok(Tail::Value(v)),
+            Err(e) => match e.caught() {
+                Some(err) => match eval_in(catch, &scope, ctx)? {
+                    Value::Function(f) => Ok(Tail::Call(
+                        f,
+                        Frame::new(Some(":catch".into()), vec![err], None),
+                    )),
+                    v => Err(Error::NotAFunction(v)),
+                },
+                None => Err(e),
+            },
+        },
+
+        Code::Chain(links) => {
+            let mut links = links.iter();
+            if let Some(target) = links.next() {
+                let mut target = eval_in(&target.code, &scope, ctx)?;
+                let mut links = links.peekable();
+                while let Some(link) = links.next() {
+                    if let Value::Function(f) = eval_in(&link.code, &scope, ctx)? {
+                        let name = f.name().map(String::from).or_else(|| link.name.clone());
+                        let frame = Frame::new(name, vec![target], link.location.as_ref());
+                        if links.peek().is_none() {
+                            return Ok(Tail::Call(f, frame));
+                        }
+                        target = f.call_traced(frame, ctx)?;
+                    }
+                }
+                Ok(Tail::Value(target))
+            } else {
+                Err(Error::NotEnoughArguments(".".into(), 2, 0))
+            }
+        }
+
+        Code::CaseOf(case, cases) => {
+            let case = eval_in(case, &scope, ctx)?;
+            match cases.select(&case, &scope, ctx)? {
+                Some((body, None)) => Ok(Tail::Eval(body.clone(), scope)),
+                Some((body, Some((names, values)))) => {
+                    Ok(Tail::Eval(body.clone(), scope.push(names, values)))
+                }
+                None => Err(Error::CaseError(case)),
+            }
+        }
+    }
+}
+
+/// The body of the selected arm, and the names it binds, if any.
+type Selected<'a> = Option<(&'a Arc<Code>, Option<(Arc<[String]>, Vec<Local>)>)>;
+
+fn arm(arm: &Option<Arm>, values: Vec<Value>) -> Selected<'_> {
+    arm.as_ref().map(|a| {
+        let values = values.into_iter().map(Local::Value).collect();
+        (&a.body, Some((a.names.clone(), values)))
+    })
+}
+
+impl Cases {
+    /// Pick the arm matching the value.
+    fn select<P: Platform>(
+        &self,
+        case: &Value,
+        scope: &Scope,
+        ctx: &Context<P>,
+    ) -> Result<Selected<'_>> {
+        if let Ok(y) = yaml::to_value(case) {
+            if let Some(body) = self.exact.get(&y) {
+                return Ok(Some((body, None)));
+            }
+        };
+
+        let selected = match case {
+            Value::Number(n) if n.is_i64() || n.is_u64() => arm(&self.integer, vec![case.clone()]),
+            Value::Number(_) => arm(&self.float, vec![case.clone()]),
+            Value::Function(_) => arm(&self.function, vec![case.clone()]),
+
+            Value::String(s) => {
+                let mut chars = s.chars();
+                match chars.next() {
+                    Some(c) => arm(
+                        &self.string,
+                        vec![c.to_string().into(), chars.collect::<String>().into()],
+                    ),
+                    None => None,
+                }
+            }
+
+            Value::List(list) => match list.split_first() {
+                Some((head, tail)) => arm(&self.list, vec![head.clone(), tail.to_vec().into()]),
+                None => None,
+            },
+
+            Value::Record(_) => match &self.record {
+                Some((paths, a)) => {
+                    // Only matches when every requested field is present.
+                    let mut fields = vec![];
+                    for path in paths {
+                        let val = match eval_in(path, scope, ctx)? {
+                            Value::String(field) => case
+                                .get_from_yaml_nested(
+                                    field.split('.').map(RecordVal::de_field_name),
+                                )
+                                .ok()
+                                .cloned(),
+                            _ => None,
+                        };
+                        match val {
+                            Some(val) => fields.push(val),
+                            None => return Ok(arm(&self.default, vec![case.clone()])),
+                        }
+                    }
+                    let values = fields.into_iter().map(Local::Value).collect();
+                    Some((&a.body, Some((a.names.clone(), values))))
+                }
+                None => None,
+            },
+
+            // Handled by exact match.
+            Value::Null | Value::Bool(_) | Value::Dict(_) => None,
+        };
+
+        Ok(selected.or_else(|| arm(&self.default, vec![case.clone()])))
+    }
+}
+
+/// Integer and floating point implementations of an arithmetic operator.
+/// Integers are computed as `i128`, so any two `u64` or `i64` operands fit,
+/// and the result is kept if it fits either. If it doesn't, the VM's
+/// `Overflow` policy decides what happens.
+pub(crate) struct Arith {
+    name: &'static str,
+    int: fn(i128, i128) -> Option<i128>,
+    f64: fn(f64, f64) -> f64,
+    zero_divisor: bool,
+    fractional: fn(&Number, &Number) -> bool,
+}
+
+impl fmt::Debug for Arith {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name)
+    }
+}
+
+pub(crate) const ADD: Arith = Arith {
+    name: "+",
+    int: i128::checked_add,
+    f64: |a, b| a + b,
+    zero_divisor: true,
+    fractional: |_, _| false,
+};
+
+pub(crate) const SUB: Arith = Arith {
+    name: "-",
+    int: i128::checked_sub,
+    f64: |a, b| a - b,
+    zero_divisor: true,
+    fractional: |_, _| false,
+};
+
+pub(crate) const MUL: Arith = Arith {
+    name: "*",
+    int: i128::checked_mul,
+    f64: |a, b| a * b,
+    zero_divisor: true,
+    fractional: |_, _| false,
+};
+
+pub(crate) const DIV: Arith = Arith {
+    name: "/",
+    int: i128::checked_div,
+    f64: |a, b| a / b,
+    zero_divisor: false,
+    fractional: |_, _| false,
+};
+
+pub(crate) const MOD: Arith = Arith {
+    name: "%",
+    int: i128::checked_rem,
+    f64: |a, b| a % b,
+    zero_divisor: false,
+    fractional: |_, _| false,
+};
+
+pub(crate) const POW: Arith = Arith {
+    name: "pow",
+    int: |a, b| u32::try_from(b).ok().and_then(|b| a.checked_pow(b)),
+    f64: f64::powf,
+    zero_divisor: true,
+    fractional: |_, exp| exp.as_i64().map(|e| e < 0).unwrap_or(false),
+};
+
+impl Arith {
+    fn apply(&self, n1: &Number, n2: &Number, overflow: Overflow) -> Result<Option<Number>> {
+        if !self.zero_divisor && (n2.as_u64() == Some(0) || n2.as_i64() == Some(0)) {
+            return Err(Error::DivisionByZero(n1.clone().into()));
+        }
+
+        let int = |n: &Number| {
+            n.as_u64()
+                .map(i128::from)
+                .or_else(|| n.as_i64().map(i128::from))
+        };
+        let ints = int(n1).zip(int(n2)).filter(|_| !(self.fractional)(n1, n2));
+        if let Some((i1, i2)) = ints {
+            let n = (self.int)(i1, i2).and_then(|i| {
+                u64::try_from(i)
+                    .map(Number::from)
+                    .or_else(|_| i64::try_from(i).map(Number::from))
+                    .ok()
+            });
+            if let Some(n) = n {
+                return Ok(Some(n));
+            }
+
+            if overflow == Overflow::Error {
+                return Err(Error::ArithmeticOverflow(
+                    self.name.into(),
+                    vec![n1.clone().into(), n2.clone().into()],
+                ));
+            }
+        }
+
+        Ok(n1
+            .as_f64()
+            .and_then(|i1| n2.as_f64().map(|i2| (self.f64)(i1, i2)))
+            .map(Number::from))
+    }
+}
+
+fn eval_arith<P: Platform>(
+    op: &Arith,
+    args: &[Arc<Code>],
+    scope: &Scope,
+    ctx: &Context<P>,
+) -> Result<Value> {
+    let mut args = args.iter();
+    if let Some(res) = args.next() {
+        let mut res = eval_in(res, scope, ctx)?;
+        for arg in args {
+            let arg = eval_in(arg, scope, ctx)?;
+            match (&res, &arg) {
+                (Value::Number(n1), Value::Number(n2)) => {
+                    if let Some(n) = op.apply(n1, n2, ctx.config.overflow)? {
+                        res = Value::Number(n);
+                    } else {
+                        return Err(Error::InvalidArguments(op.name.into(), vec![res, arg]));
+                    }
+                }
+                (n1, n2) => {
+                    return Err(Error::InvalidArguments(
+                        op.name.into(),
+                        vec![n1.clone(), n2.clone()],
+                    ))
+                }
+            }
+        }
+        Ok(res)
+    } else {
+        Err(Error::NotEnoughArguments(op.name.into(), 1, 0))
+    }
+}
+
+fn eval_compare<P: Platform>(
+    name: &str,
+    args: &[Arc<Code>],
+    scope: &Scope,
+    ctx: &Context<P>,
+    test: fn(Ordering) -> bool,
+) -> Result<Value> {
+    if args.len() != 2 {
+        return Err(Error::NotEnoughArguments(name.into(), 2, args.len()));
+    }
+
+    let arg1 = eval_in(&args[0], scope, ctx)?;
+    let arg2 = eval_in(&args[1], scope, ctx)?;
+    if let Some(ord) = arg1.compare(&arg2) {
+        Ok(Value::Bool(test(ord)))
+    } else {
+        Err(Error::InvalidArguments(name.into(), vec![arg1, arg2]))
+    }
+}
@@ -1,4 +1,433 @@
+use crate::compile::{self, Code, Scope, WeakScope};
 use crate::expr::Expr;
-use std::collections::HashMap;
+use crate::platform::Platform;
+use crate::value::Record;
+use crate::vm::Context;
+use crate::{Error, Function, Result, Value};
+use indexmap::IndexMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, Weak};
+use std::thread::{self, ThreadId};
 
-pub type Env = HashMap<String, Expr>;
+/// The names in scope.
+///
+/// Scopes are chained to their parents, and shared between everything that
+/// captured them. Cloning is cheap, and adding a name only touches the
+/// innermost scope, creating a new one if the current one is shared.
+#[derive(Clone, Default)]
+pub struct Env(Option<Arc<Names>>);
+
+/// An `Env` that doesn't keep its names alive.
+#[derive(Clone, Default)]
+pub(crate) struct WeakEnv(Option<Weak<Names>>);
+
+impl WeakEnv {
+    pub(crate) fn upgrade(&self) -> Option<Env> {
+        match &self.0 {
+            Some(names) => names.upgrade().map(|n| Env(Some(n))),
+            None => Some(Env(None)),
+        }
+    }
+}
+
+struct Names {
+    names: HashMap<String, Binding>,
+    parent: Env,
+}
+
+/// An expression, and the thunk evaluating it once it's needed.
+struct Binding {
+    expr: Expr,
+    thunk: OnceLock<Thunk>,
+}
+
+impl From<Expr> for Binding {
+    fn from(expr: Expr) -> Self {
+        Self {
+            expr,
+            thunk: OnceLock::new(),
+        }
+    }
+}
+
+impl Env {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Expr> {
+        self.binding(name).map(|(b, _)| &b.expr)
+    }
+
+    /// The value bound to the name. The expression is evaluated the first
+    /// time it's needed, in the scope it was added to, and only once.
+    pub(crate) fn get_thunk(&self, name: &str) -> Option<Thunk> {
+        self.binding(name).map(|(b, env)| {
+            b.thunk
+                .get_or_init(|| {
+                    // The scope holds the thunk, so the thunk only refers
+                    // back to it weakly.
+                    let thunk = Thunk::new(name.into(), compile::compile_named(name, &b.expr));
+                    thunk.bind_global(&Scope::new(env.clone()));
+                    thunk
+                })
+                .clone()
+        })
+    }
+
+    pub(crate) fn downgrade(&self) -> WeakEnv {
+        WeakEnv(self.0.as_ref().map(Arc::downgrade))
+    }
+
+    fn binding(&self, name: &str) -> Option<(&Binding, &Env)> {
+        let mut env = self;
+        while let Some(scope) = &env.0 {
+            if let Some(binding) = scope.names.get(name) {
+                return Some((binding, env));
+            }
+            env = &scope.parent;
+        }
+        None
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    pub fn insert(&mut self, name: String, expr: Expr) {
+        if let Some(scope) = self.0.as_mut().and_then(Arc::get_mut) {
+            scope.names.insert(name, expr.into());
+        } else {
+            let mut names = HashMap::new();
+            names.insert(name, expr.into());
+            let parent = Self(self.0.take());
+            self.0 = Some(Arc::new(Names { names, parent }));
+        }
+    }
+
+    /// The names in scope, innermost first, without the shadowed ones.
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Expr)> {
+        let mut seen = HashSet::new();
+        let mut env = self;
+        let mut scopes = vec![];
+        while let Some(scope) = &env.0 {
+            scopes.push(scope);
+            env = &scope.parent;
+        }
+        scopes
+            .into_iter()
+            .flat_map(|s| s.names.iter())
+            .filter(move |(k, _)| seen.insert(*k))
+            .map(|(k, b)| (k, &b.expr))
+    }
+
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+}
+
+impl Extend<(String, Expr)> for Env {
+    fn extend<I: IntoIterator<Item = (String, Expr)>>(&mut self, iter: I) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl std::iter::FromIterator<(String, Expr)> for Env {
+    fn from_iter<I: IntoIterator<Item = (String, Expr)>>(iter: I) -> Self {
+        let mut env = Self::new();
+        env.extend(iter);
+        env
+    }
+}
+
+impl PartialEq for Env {
+    fn eq(&self, other: &Self) -> bool {
+        match (&self.0, &other.0) {
+            (Some(a), Some(b)) if Arc::ptr_eq(a, b) => true,
+            _ => self.len() == other.len() && self.iter().all(|(k, v)| other.get(k) == Some(v)),
+        }
+    }
+}
+
+impl fmt::Debug for Env {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// A binding that is evaluated the first time it is needed, and only once.
+#[derive(Clone)]
+pub struct Thunk(Arc<ThunkInner>);
+
+struct ThunkInner {
+    name: String,
+    state: Mutex<State>,
+    /// A binding to a record has one for each field, so that the fields are
+    /// evaluated only when they're needed too.
+    fields: OnceLock<Vec<(String, Thunk)>>,
+}
+
+enum State {
+    Pending(Arc<Code>, Bound),
+    Forcing(ThreadId, Arc<Code>, Bound),
+    Done(Value),
+}
+
+/// The scope a binding is evaluated in, and where its value is kept.
+#[derive(Clone)]
+enum Bound {
+    /// Kept in the binding.
+    Strong(Scope),
+    /// For bindings stored in the scope itself, which would otherwise keep
+    /// each other alive forever. Kept by the evaluation.
+    Local(WeakScope),
+    /// Like `Local`, for the names of an `Env`. Kept by the VM.
+    Global(WeakScope),
+}
+
+impl Bound {
+    fn scope(&self) -> Option<Scope> {
+        match self {
+            Self::Strong(scope) => Some(scope.clone()),
+            Self::Local(scope) | Self::Global(scope) => scope.upgrade(),
+        }
+    }
+
+    fn memo<'a, P: Platform>(&self, ctx: &'a Context<P>) -> Option<&'a Memo> {
+        match self {
+            Self::Strong(_) => None,
+            Self::Local(_) => Some(&ctx.memo),
+            Self::Global(_) => Some(ctx.globals),
+        }
+    }
+}
+
+impl Thunk {
+    pub(crate) fn new(name: String, code: Arc<Code>) -> Self {
+        Self(Arc::new(ThunkInner {
+            name,
+            state: Mutex::new(State::Pending(code, Bound::Strong(Scope::default()))),
+            fields: OnceLock::new(),
+        }))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    /// Set the scope the binding will be evaluated in.
+    pub(crate) fn bind(&self, scope: Scope) {
+        self.bind_to(Bound::Strong(scope))
+    }
+
+    /// Set the scope the binding will be evaluated in, when the binding is
+    /// stored in that scope. Its value is kept until the end of the
+    /// evaluation, since it can refer back to the scope, e.g. as a function.
+    pub(crate) fn bind_local(&self, scope: &Scope) {
+        self.bind_to(Bound::Local(scope.downgrade()))
+    }
+
+    /// Like `bind_local`, with the value kept for as long as the VM.
+    pub(crate) fn bind_global(&self, scope: &Scope) {
+        self.bind_to(Bound::Global(scope.downgrade()))
+    }
+
+    fn bind_to(&self, bound: Bound) {
+        if let State::Pending(_, b) = &mut *self.state() {
+            *b = bound;
+        }
+    }
+
+    pub fn force<P: Platform>(&self, ctx: &Context<P>) -> Result<Value> {
+        let current = thread::current().id();
+        let (code, bound, scope) = {
+            let mut state = self.state();
+            let (code, bound) = match &*state {
+                State::Done(v) => return Ok(v.clone()),
+                State::Forcing(id, _, _) if *id == current => {
+                    return Err(Error::CyclicBinding(self.name().into()))
+                }
+                // Another thread got here first. Evaluating the binding again
+                // gives the same result.
+                State::Forcing(_, code, bound) => (code.clone(), bound.clone()),
+                State::Pending(code, bound) => (code.clone(), bound.clone()),
+            };
+            if let Some(v) = bound.memo(ctx).and_then(|m| m.get(self)) {
+                return Ok(v);
+            }
+            // Nothing can refer to a binding of a scope that's gone.
+            let scope = bound
+                .scope()
+                .ok_or_else(|| Error::Undefined(self.name().into()))?;
+            // A function is as cheap to make again as to look up.
+            if let (Some(_), Some(lambda)) = (bound.memo(ctx), code.as_lambda()) {
+                return Ok(Function::from_lambda(lambda.clone(), scope).into());
+            }
+            if let State::Pending(..) = &*state {
+                *state = State::Forcing(current, code.clone(), bound.clone());
+            }
+            (code, bound, scope)
+        };
+
+        let res = match self.fields(&code, &bound) {
+            Some(fields) => fields
+                .iter()
+                .map(|(k, t)| Ok((k.clone(), t.force(ctx)?)))
+                .collect::<Result<IndexMap<String, Value>>>()
+                .map(Value::from),
+            None => compile::eval(code, scope, ctx),
+        };
+
+        let mut state = self.state();
+        match (&*state, res, bound.memo(ctx)) {
+            (State::Done(v), _, _) => Ok(v.clone()),
+            (_, Ok(v), None) => {
+                *state = State::Done(v.clone());
+                Ok(v)
+            }
+            (State::Forcing(id, code, _), res, memo) if *id == current => {
+                if let (Ok(v), Some(memo)) = (&res, memo) {
+                    memo.insert(self, v.clone());
+                }
+                *state = State::Pending(code.clone(), bound);
+                res
+            }
+            (_, res, memo) => {
+                if let (Ok(v), Some(memo)) = (&res, memo) {
+                    memo.insert(self, v.clone());
+                }
+                res
+            }
+        }
+    }
+
+    /// The value of a field of the binding, evaluating only that field when
+    /// the binding is a record that isn't evaluated yet.
+    pub(crate) fn force_field<P: Platform>(&self, path: &str, ctx: &Context<P>) -> Result<Value> {
+        let mut thunk = self.clone();
+        let mut path = path;
+        loop {
+            let (first, rest) = match path.split_once('.') {
+                Some((first, rest)) => (first, Some(rest)),
+                None => (path, None),
+            };
+            let name = Record::ser_field_name(&Record::de_field_name(first)?);
+            match (thunk.field(&name), rest) {
+                (Some(field), Some(rest)) => {
+                    thunk = field;
+                    path = rest;
+                }
+                (Some(field), None) => return field.force(ctx),
+                (None, _) => break,
+            }
+        }
+        let val = thunk.force(ctx)?;
+        val.get_from_yaml_nested(path.split('.').map(Record::de_field_name))
+            .cloned()
+    }
+
+    fn field(&self, name: &str) -> Option<Thunk> {
+        let (code, bound) = match &*self.state() {
+            State::Pending(code, bound) | State::Forcing(_, code, bound) => {
+                (code.clone(), bound.clone())
+            }
+            State::Done(_) => return None,
+        };
+        let fields = self.fields(&code, &bound)?;
+        fields
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, t)| t.clone())
+    }
+
+    /// The bindings of the fields, when the binding is a record.
+    fn fields(&self, code: &Code, bound: &Bound) -> Option<&[(String, Thunk)]> {
+        let items = code.as_record()?;
+        let fields = self.0.fields.get_or_init(|| {
+            items
+                .iter()
+                .map(|(k, code)| {
+                    let thunk = Thunk::new(format!("{}.{}", self.name(), k), code.clone());
+                    thunk.bind_to(bound.clone());
+                    (k.clone(), thunk)
+                })
+                .collect()
+        });
+        Some(fields)
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.0.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// The values of bindings, kept apart from the scopes the bindings are in,
+/// so that a value referring back to its scope, like a function, doesn't
+/// keep it alive after the evaluation or the VM holding the values is gone.
+#[derive(Default)]
+pub(crate) struct Memo(Mutex<Values>);
+
+#[derive(Default)]
+struct Values {
+    /// By the address of the binding, which the weak reference keeps from
+    /// being reused.
+    values: HashMap<usize, (Weak<ThunkInner>, Value)>,
+    /// The number of values at which those of bindings that are gone are
+    /// dropped.
+    prune_at: usize,
+}
+
+impl Memo {
+    fn get(&self, thunk: &Thunk) -> Option<Value> {
+        let values = self.values();
+        values.values.get(&thunk.addr()).map(|(_, v)| v.clone())
+    }
+
+    fn insert(&self, thunk: &Thunk, value: Value) {
+        let mut values = self.values();
+        if values.values.len() >= values.prune_at {
+            values.values.retain(|_, (t, _)| t.strong_count() > 0);
+            values.prune_at = (values.values.len() * 2).max(64);
+        }
+        let entry = (Arc::downgrade(&thunk.0), value);
+        values.values.insert(thunk.addr(), entry);
+    }
+
+    fn values(&self) -> MutexGuard<'_, Values> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Thunk {
+    fn addr(&self) -> usize {
+        Arc::as_ptr(&self.0) as usize
+    }
+}
+
+impl PartialEq for Thunk {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl fmt::Debug for Thunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Thunk").field(&self.0.name).finish()
+    }
+}
+
+impl fmt::Display for Thunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &*self.state() {
+            State::Done(v) => v.fmt(f),
+            _ => f.write_str(self.name()),
+        }
+    }
+}
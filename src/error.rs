@@ -1,5 +1,9 @@
+use crate::loader::Location;
 use crate::yaml::Error as YamlError;
 use crate::{Expr, Value, Yaml};
+use std::fmt;
+use std::io;
+use std::time::Duration;
 use thiserror::Error as ThisError;
 
 #[derive(ThisError, Debug)]
@@ -28,14 +32,53 @@ pub enum Error {
     #[error("{0} is not a record")]
     NotARecordExpr(Expr),
 
+    #[error("{0} is not a dict")]
+    NotADict(Value),
+
+    #[error("{0} cannot be a key")]
+    InvalidKey(Value),
+
     #[error("{0} was called with invalid arguments: {1:?}")]
     InvalidArguments(String, Vec<Value>),
 
     #[error("{0} requires at-least {1} arguments, but {2} was provided")]
     NotEnoughArguments(String, usize, usize),
 
+    #[error("{0} overflowed the integer range with arguments: {1:?}")]
+    ArithmeticOverflow(String, Vec<Value>),
+
+    #[error("{0} cannot be divided by zero")]
+    DivisionByZero(Value),
+
+    #[error("{0} is defined in terms of itself")]
+    CyclicBinding(String),
+
     #[error("{0} is not being handled in the case-of statement")]
     CaseError(Value),
+
+    #[error("evaluation took more than {0} steps")]
+    StepLimitExceeded(u64),
+
+    #[error("evaluation went deeper than {0} levels")]
+    DepthLimitExceeded(usize),
+
+    #[error("evaluation took longer than {0:?}")]
+    Timeout(Duration),
+
+    #[error("{0} was raised")]
+    Raised(Value),
+
+    #[error("module {0} was not found")]
+    ModuleNotFound(String),
+
+    #[error("{0} imports itself, directly or through other modules")]
+    CyclicImport(String),
+
+    #[error("cannot read {0}: {1}")]
+    IoError(String, io::Error),
+
+    #[error("{0} is not allowed")]
+    CapabilityDenied(String),
     // #[error("the data for key `{0}` is not available")]
     // Redaction(String),
     // #[error("invalid header (expected {expected:?}, found {found:?})")]
@@ -48,6 +91,132 @@ pub enum Error {
     //
     #[error("{0}")]
     PlatformCallError(String),
+
+    #[error("{error} (at {location})")]
+    Located {
+        error: Box<Error>,
+        location: Location,
+    },
+
+    #[error("{error}")]
+    Traced {
+        error: Box<Error>,
+        trace: Vec<Frame>,
+    },
+}
+
+/// A function call that was in progress when an error occurred.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub name: Option<String>,
+    pub args: Vec<Value>,
+    pub location: Option<Location>,
+    /// For a frame standing for the calls in tail position that were left
+    /// out of the trace, how many there were.
+    pub elided: usize,
+}
+
+impl Frame {
+    pub fn new(name: Option<String>, args: Vec<Value>, location: Option<&Location>) -> Self {
+        Self {
+            name,
+            args,
+            location: location.cloned(),
+            elided: 0,
+        }
+    }
+
+    pub fn elided(count: usize) -> Self {
+        Self {
+            name: None,
+            args: vec![],
+            location: None,
+            elided: count,
+        }
+    }
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.elided {
+            0 => {}
+            1 => return f.write_str("1 tail call elided"),
+            n => return write!(f, "{} tail calls elided", n),
+        }
+        f.write_str(self.name.as_deref().unwrap_or("<anonymous>"))?;
+        for arg in &self.args {
+            write!(f, " {}", arg)?;
+        }
+        if let Some(location) = &self.location {
+            write!(f, " (at {})", location)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error {
+    /// Attach the location of the failed expression, unless the error already
+    /// points to a more specific one.
+    pub fn at(self, location: Location) -> Self {
+        match self {
+            e if e.location().is_some() => e,
+            Self::Traced { error, trace } => Self::Traced {
+                error: Box::new(error.at(location)),
+                trace,
+            },
+            e => Self::Located {
+                error: Box::new(e),
+                location,
+            },
+        }
+    }
+
+    /// Record a call the error propagated through, innermost first.
+    pub fn with_frame(self, frame: Frame) -> Self {
+        match self {
+            Self::Traced { error, mut trace } => {
+                trace.push(frame);
+                Self::Traced { error, trace }
+            }
+            e => Self::Traced {
+                error: Box::new(e),
+                trace: vec![frame],
+            },
+        }
+    }
+
+    pub fn location(&self) -> Option<&Location> {
+        match self {
+            Self::Located { location, .. } => Some(location),
+            Self::Traced { error, .. } => error.location(),
+            _ => None,
+        }
+    }
+
+    pub fn trace(&self) -> &[Frame] {
+        match self {
+            Self::Traced { trace, .. } => trace,
+            _ => &[],
+        }
+    }
+
+    /// The value a `:catch` gets for the error: the raised value, or else
+    /// the error message. Going over a limit can't be caught.
+    pub fn caught(&self) -> Option<Value> {
+        match self.root() {
+            Self::Raised(v) => Some(v.clone()),
+            Self::StepLimitExceeded(_) | Self::DepthLimitExceeded(_) | Self::Timeout(_) => None,
+            e => Some(Value::String(e.to_string())),
+        }
+    }
+
+    /// The underlying error, without any source or stack information.
+    pub fn root(&self) -> &Self {
+        match self {
+            Self::Located { error, .. } | Self::Traced { error, .. } => error.root(),
+            e => e,
+        }
+    }
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
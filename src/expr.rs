@@ -1,12 +1,18 @@
+use crate::compile::{self, Arm, Cases, Code, CompiledExpr, Link, Piece, Scopes};
+use crate::compile::{ADD, DIV, MOD, MUL, POW, SUB};
+use crate::loader::{Location, Node, Origin};
 use crate::platform::Platform;
 use crate::value::{Function, Record as RecordVal};
+use crate::vm::Context;
 use crate::{yaml, Env, Value, Yaml};
 use crate::{Error, Result};
 use indexmap::IndexMap;
 use indexmap::IndexSet;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, Serializer};
 use serde_json as json;
+use std::cmp::Ordering;
 use std::fmt;
+use std::sync::Arc;
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(untagged)]
@@ -17,8 +23,19 @@ pub enum Expr {
     IfElse(Box<IfElse>),
     LetIn(Box<LetIn>),
     Add(Add),
+    Sub(Sub),
+    Mul(Mul),
+    Div(Div),
+    Mod(Mod),
+    Pow(Pow),
     Append(Append),
     Equals(Equals),
+    Lt(Lt),
+    Le(Le),
+    Gt(Gt),
+    Ge(Ge),
+    And(And),
+    Or(Or),
     Constant(Constant),
     Variable(String),
     List(Box<List>),
@@ -28,7 +45,17 @@ pub enum Expr {
     PlatformCall(Box<PlatformCall>),
     Chain(Box<Chain>),
     CaseOf(Box<CaseOf>),
+    Import(Box<Import>),
+    Native(Box<Native>),
+    Fmt(Box<Fmt>),
+    Raise(Box<Raise>),
+    Try(Box<Try>),
+    #[serde(skip_deserializing)]
+    Located(Box<Located>),
     Value(#[serde(skip)] Value),
+    /// A module, or a field of one, like `Basics.add`.
+    #[serde(skip)]
+    Module(String),
 }
 
 impl From<Box<PlatformCall>> for Expr {
@@ -47,7 +74,16 @@ impl fmt::Display for Expr {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::Value(v) => v.fmt(f),
-            e => f.write_str(&json::to_string(e).unwrap()),
+            Self::Module(path) => f.write_str(path),
+            // JSON only has string keys, YAML has any.
+            e => match json::to_string(e) {
+                Ok(s) => f.write_str(&s),
+                Err(_) => f.write_str(
+                    yaml::to_string(e)
+                        .map_err(|_| fmt::Error)?
+                        .trim_start_matches("---\n"),
+                ),
+            },
         }
     }
 }
@@ -89,350 +125,409 @@ impl From<Box<Lambda>> for Expr {
 }
 
 impl Expr {
-    pub fn eval<P: Platform>(self, mut env: Env, platform: &P) -> Result<Value> {
+    pub fn location(&self) -> Option<&Location> {
         match self {
-            Self::Value(v) => Ok(v),
-            Self::Constant(y) => Ok(y.yaml.into()),
-            Self::List(l) => {
-                let mut items = vec![];
-                for i in l.items {
-                    let val = i.eval(env.clone(), platform)?;
-                    items.push(val);
-                }
-                Ok(Value::List(items.into()))
-            }
+            Self::Located(l) => Some(&l.location),
+            _ => None,
+        }
+    }
 
-            Self::Record(r) => {
-                let mut items = IndexMap::new();
-                for (k, v) in r.items {
-                    let val = v.eval(env.clone(), platform)?;
-                    items.insert(k, val);
-                }
-                Ok(Value::Record(items.into()))
-            }
-
-            Self::Lambda(l) => Ok(Value::Function(Box::new(l.to_function(env)))),
-
-            Self::Variable(name) => {
-                if let Some((first, rest)) = name.split_once('.') {
-                    if let Some(e) = env.get(first) {
-                        let val = e.clone().eval(env.clone(), platform)?;
-                        val.get_from_yaml_nested(rest.split('.').map(RecordVal::de_field_name))
-                            .map(Value::clone)
-                    } else {
-                        Err(Error::Undefined(first.into()))
-                    }
-                } else {
-                    match env.get(&name).cloned() {
-                        Some(Expr::Value(v)) => Ok(v),
-                        Some(e) => e.eval(env, platform),
-                        None => Err(Error::Undefined(name.clone())),
-                    }
-                }
-            }
+    pub fn unlocated(self) -> Self {
+        match self {
+            Self::Located(l) => l.expr.unlocated(),
+            e => e,
+        }
+    }
 
-            Self::IfElse(cond) => {
-                let res = cond.if_.eval(env.clone(), platform)?;
-                match res {
-                    Value::Bool(true) => cond.then.eval(env, platform),
-                    Value::Bool(false) => cond.else_.eval(env, platform),
-                    v => Err(Error::NotABoolean(v)),
-                }
-            }
+    /// The name a called expression is bound to, if any.
+    fn name(&self) -> Option<String> {
+        match self {
+            Self::Variable(name) | Self::Module(name) => Some(name.clone()),
+            Self::Call(call) => call.first().and_then(Self::name),
+            Self::Located(l) => l.expr.name(),
+            _ => None,
+        }
+    }
 
-            Self::LetIn(letin) => {
-                for (k, v) in &letin.let_ {
-                    let val = v.clone();
-                    env.insert(k.into(), val);
+    pub(crate) fn locate(self, node: &Node, origin: &Option<Arc<Origin>>) -> Self {
+        let expr = match self {
+            Self::Value(_) | Self::Constant(_) | Self::Located(_) | Self::Module(_) => return self,
+            Self::Variable(_) => self,
+            Self::Call(args) => Self::Call(locate_all(args, node.items(), origin)),
+            Self::Lambda(mut l) => {
+                l.do_ = locate_opt(l.do_, node.get(&[":do"]), origin);
+                Self::Lambda(l)
+            }
+            Self::IfElse(mut c) => {
+                c.if_ = locate_opt(c.if_, node.get(&[":if"]), origin);
+                c.then = locate_opt(c.then, node.get(&[":then"]), origin);
+                c.else_ = locate_opt(c.else_, node.get(&[":else"]), origin);
+                Self::IfElse(c)
+            }
+            Self::LetIn(mut l) => {
+                if let Some(n) = node.get(&[":let"]) {
+                    l.let_ = locate_map(l.let_, n, origin);
                 }
-                let res = letin.in_.eval(env, platform);
-                res
-            }
-
-            Self::Call(call) => {
-                if let Some((func, args)) = call.split_first() {
-                    let val = func.clone().eval(env.clone(), platform)?;
-                    if let Value::Function(f) = val {
-                        f.to_owned().call(
-                            args.into_iter()
-                                .map(|a| a.clone().eval(env.clone(), platform)),
-                            platform,
-                        )
-                    } else {
-                        Err(Error::NotAFunction(val))
-                    }
-                } else {
-                    Err(Error::NoFunction)
+                l.in_ = locate_opt(l.in_, node.get(&[":in"]), origin);
+                Self::LetIn(l)
+            }
+            Self::Add(mut a) => {
+                let n = node.get(&[":add", ":+"]);
+                a.args = locate_all(a.args, n.map(Node::items).unwrap_or_default(), origin);
+                Self::Add(a)
+            }
+            Self::Sub(mut a) => {
+                let n = node.get(&[":sub", ":-"]);
+                a.args = locate_all(a.args, n.map(Node::items).unwrap_or_default(), origin);
+                Self::Sub(a)
+            }
+            Self::Mul(mut a) => {
+                let n = node.get(&[":mul", ":*"]);
+                a.args = locate_all(a.args, n.map(Node::items).unwrap_or_default(), origin);
+                Self::Mul(a)
+            }
+            Self::Div(mut a) => {
+                let n = node.get(&[":div", ":/"]);
+                a.args = locate_all(a.args, n.map(Node::items).unwrap_or_default(), origin);
+                Self::Div(a)
+            }
+            Self::Mod(mut a) => {
+                let n = node.get(&[":mod", ":%"]);
+                a.args = locate_all(a.args, n.map(Node::items).unwrap_or_default(), origin);
+                Self::Mod(a)
+            }
+            Self::Pow(mut a) => {
+                let n = node.get(&[":pow"]);
+                a.args = locate_all(a.args, n.map(Node::items).unwrap_or_default(), origin);
+                Self::Pow(a)
+            }
+            Self::Append(mut a) => {
+                let n = node.get(&[":append", ":++"]);
+                a.args = locate_all(a.args, n.map(Node::items).unwrap_or_default(), origin);
+                Self::Append(a)
+            }
+            Self::Equals(mut e) => {
+                let n = node.get(&[":eq", ":=="]);
+                e.args = locate_all(e.args, n.map(Node::items).unwrap_or_default(), origin);
+                Self::Equals(e)
+            }
+            Self::Lt(mut c) => {
+                let n = node.get(&[":lt", ":<"]);
+                c.args = locate_all(c.args, n.map(Node::items).unwrap_or_default(), origin);
+                Self::Lt(c)
+            }
+            Self::Le(mut c) => {
+                let n = node.get(&[":le", ":<="]);
+                c.args = locate_all(c.args, n.map(Node::items).unwrap_or_default(), origin);
+                Self::Le(c)
+            }
+            Self::Gt(mut c) => {
+                let n = node.get(&[":gt", ":>"]);
+                c.args = locate_all(c.args, n.map(Node::items).unwrap_or_default(), origin);
+                Self::Gt(c)
+            }
+            Self::Ge(mut c) => {
+                let n = node.get(&[":ge", ":>="]);
+                c.args = locate_all(c.args, n.map(Node::items).unwrap_or_default(), origin);
+                Self::Ge(c)
+            }
+            Self::And(mut a) => {
+                let n = node.get(&[":and", ":&&"]);
+                a.args = locate_all(a.args, n.map(Node::items).unwrap_or_default(), origin);
+                Self::And(a)
+            }
+            Self::Or(mut o) => {
+                let n = node.get(&[":or", ":||"]);
+                o.args = locate_all(o.args, n.map(Node::items).unwrap_or_default(), origin);
+                Self::Or(o)
+            }
+            Self::Chain(mut c) => {
+                let n = node.get(&[":chain", ":|>"]);
+                c.args = locate_all(c.args, n.map(Node::items).unwrap_or_default(), origin);
+                Self::Chain(c)
+            }
+            Self::List(mut l) => {
+                let n = node.get(&[":list"]);
+                l.items = locate_all(l.items, n.map(Node::items).unwrap_or_default(), origin);
+                Self::List(l)
+            }
+            Self::Record(mut r) => {
+                if let Some(n) = node.get(&[":rec"]) {
+                    r.items = locate_map(r.items, n, origin);
                 }
+                Self::Record(r)
             }
-
-            Self::Add(s) => {
-                let mut args = s.args.into_iter();
-                if let Some(sum) = args.next().map(|a| a.eval(env.clone(), platform)) {
-                    let mut sum = sum?;
-                    for arg in args {
-                        let arg = arg.eval(env.clone(), platform)?;
-                        match (&sum, &arg) {
-                            (Value::Number(n1), Value::Number(n2)) => {
-                                if let Some(s) = n1
-                                    .as_u64()
-                                    .and_then(|i1| n2.as_u64().map(|i2| (i1 + i2).into()))
-                                    .or_else(|| {
-                                        n1.as_i64()
-                                            .and_then(|i1| n2.as_i64().map(|i2| (i1 + i2).into()))
-                                    })
-                                    .or_else(|| {
-                                        n1.as_f64()
-                                            .and_then(|i1| n2.as_f64().map(|i2| (i1 + i2).into()))
-                                    })
-                                {
-                                    sum = Value::Number(s);
-                                } else {
-                                    return Err(Error::InvalidArguments(
-                                        "+".into(),
-                                        vec![sum, arg],
-                                    ));
-                                }
-                            }
-                            (n1, n2) => {
-                                return Err(Error::InvalidArguments(
-                                    "+".into(),
-                                    vec![n1.clone(), n2.clone()],
-                                ))
-                            }
-                        }
-                    }
-                    Ok(sum)
-                } else {
-                    Err(Error::NotEnoughArguments("+".into(), 1, 0))
+            Self::With(mut w) => {
+                w.do_ = locate_opt(w.do_, node.get(&[":do"]), origin);
+                Self::With(w)
+            }
+            Self::Update(mut u) => {
+                u.update = locate_opt(u.update, node.get(&[":update"]), origin);
+                if let Some(n) = node.get(&[":set"]) {
+                    u.set = locate_map(u.set, n, origin);
                 }
+                Self::Update(u)
             }
-
-            Self::Append(s) => {
-                let mut args = s.args.into_iter();
-                if let Some(sum) = args.next().map(|a| a.eval(env.clone(), platform)) {
-                    let mut sum = sum?;
-                    for arg in args {
-                        let arg = arg.eval(env.clone(), platform)?;
-                        match (&sum, &arg) {
-                            (Value::List(l1), Value::List(l2)) => {
-                                let mut list = l1.0.clone();
-                                list.append(&mut l2.0.clone());
-                                sum = Value::List(list.into());
-                            }
-                            (Value::String(s1), Value::String(s2)) => {
-                                sum = Value::String(format!("{}{}", s1, s2));
-                            }
-                            (n1, n2) => {
-                                return Err(Error::InvalidArguments(
-                                    "++".into(),
-                                    vec![n1.clone(), n2.clone()],
-                                ))
-                            }
-                        }
-                    }
-                    Ok(sum)
-                } else {
-                    Err(Error::NotEnoughArguments("++".into(), 1, 0))
+            Self::PlatformCall(mut p) => {
+                p.arg = locate_opt(p.arg, node.get(&[":arg"]), origin);
+                p.from = origin.clone();
+                Self::PlatformCall(p)
+            }
+            Self::CaseOf(mut c) => {
+                c.case = locate_opt(c.case, node.get(&[":case"]), origin);
+                if let Some(n) = node.get(&[":of"]) {
+                    c.of = c.of.locate(n, origin);
                 }
+                Self::CaseOf(c)
+            }
+            Self::Import(mut i) => {
+                i.from = origin.clone();
+                Self::Import(i)
+            }
+            Self::Native(_) | Self::Fmt(_) => self,
+            Self::Raise(mut r) => {
+                r.raise = locate_opt(r.raise, node.get(&[":raise"]), origin);
+                Self::Raise(r)
             }
+            Self::Try(mut t) => {
+                t.try_ = locate_opt(t.try_, node.get(&[":try"]), origin);
+                t.catch = locate_opt(t.catch, node.get(&[":catch"]), origin);
+                Self::Try(t)
+            }
+        };
+
+        Self::Located(Box::new(Located {
+            expr,
+            location: node.location(origin),
+        }))
+    }
+
+    pub fn eval<P: Platform>(self, env: Env, ctx: &Context<P>) -> Result<Value> {
+        CompiledExpr::new(&self).eval(env, ctx)
+    }
 
-            Self::Equals(e) => {
-                if e.args.len() != 2 {
-                    Err(Error::NotEnoughArguments("+".into(), 2, e.args.len()))
-                } else {
-                    let arg1 = e.args[0].clone().eval(env.clone(), platform)?;
-                    let arg2 = e.args[1].clone().eval(env, platform)?;
-                    Ok(Yaml::Bool(arg1 == arg2).into())
+    /// Compile the expression, resolving the names bound in the scopes.
+    pub(crate) fn compile(&self, scopes: &mut Scopes) -> Arc<Code> {
+        let code = match self {
+            Self::Value(v) => Code::Value(v.clone()),
+            Self::Constant(c) => Code::Value(c.yaml.clone().into()),
+            Self::Located(l) => Code::Located(l.expr.compile(scopes), l.location.clone()),
+
+            Self::Variable(name) => match name.split_once('.') {
+                Some((first, rest)) => Code::Field(Arc::new(scopes.resolve(first)), rest.into()),
+                None => scopes.resolve(name),
+            },
+
+            Self::Module(path) => match path.split_once('.') {
+                Some((module, rest)) => {
+                    Code::Field(Arc::new(Code::Module(module.into())), rest.into())
                 }
+                None => Code::Module(path.clone()),
+            },
+
+            Self::Call(call) => Code::Call(compile_all(call, scopes), self.name()),
+            Self::Lambda(l) => Code::Lambda(Arc::new(l.compile(None, scopes))),
+
+            Self::IfElse(c) => Code::IfElse(
+                c.if_.compile(scopes),
+                c.then.compile(scopes),
+                c.else_.compile(scopes),
+            ),
+
+            Self::LetIn(l) => {
+                let names: Arc<[String]> = l.let_.keys().cloned().collect();
+                scopes.nested(Some(names.clone()), |scopes| {
+                    let values = l
+                        .let_
+                        .iter()
+                        .map(|(k, v)| v.compile_named(k, scopes))
+                        .collect();
+                    Code::LetIn(names, values, l.in_.compile(scopes))
+                })
             }
 
+            Self::Add(a) => Code::Arith(&ADD, compile_all(&a.args, scopes)),
+            Self::Sub(a) => Code::Arith(&SUB, compile_all(&a.args, scopes)),
+            Self::Mul(a) => Code::Arith(&MUL, compile_all(&a.args, scopes)),
+            Self::Div(a) => Code::Arith(&DIV, compile_all(&a.args, scopes)),
+            Self::Mod(a) => Code::Arith(&MOD, compile_all(&a.args, scopes)),
+            Self::Pow(a) => Code::Arith(&POW, compile_all(&a.args, scopes)),
+            Self::Append(a) => Code::Append(compile_all(&a.args, scopes)),
+            Self::Equals(e) => Code::Equals(compile_all(&e.args, scopes)),
+            Self::Lt(c) => Code::Compare("<", Ordering::is_lt, compile_all(&c.args, scopes)),
+            Self::Le(c) => Code::Compare("<=", Ordering::is_le, compile_all(&c.args, scopes)),
+            Self::Gt(c) => Code::Compare(">", Ordering::is_gt, compile_all(&c.args, scopes)),
+            Self::Ge(c) => Code::Compare(">=", Ordering::is_ge, compile_all(&c.args, scopes)),
+            Self::And(a) => Code::And(compile_all(&a.args, scopes)),
+            Self::Or(o) => Code::Or(compile_all(&o.args, scopes)),
+            Self::List(l) => Code::List(compile_all(&l.items, scopes)),
+            Self::Record(r) => Code::Record(compile_map(&r.items, scopes)),
+
             Self::With(w) => {
-                for name in w.with {
-                    match env.get(&name).cloned() {
-                        Some(Self::Record(r)) => {
-                            for (k, v) in r.items {
-                                env.insert(k, v);
-                            }
-                        }
-
-                        Some(Self::Constant(Constant { yaml })) => match yaml {
-                            Yaml::Mapping(m) => {
-                                for (k, v) in m {
-                                    if let Yaml::String(s) = k {
-                                        env.insert(s, Self::Constant(v.into()));
-                                    }
-                                }
-                            }
-                            _ => {}
-                        },
-                        Some(e) => return Err(Error::NotARecordExpr(e)),
-                        None => return Err(Error::Undefined(name.into())),
-                    }
-                }
-                w.do_.eval(env, platform)
+                let with = w
+                    .with
+                    .iter()
+                    .map(|name| Self::Variable(name.clone()).compile(scopes))
+                    .collect();
+                Code::With(with, scopes.nested(None, |scopes| w.do_.compile(scopes)))
             }
 
-            Expr::Update(u) => {
-                let rec = u.update.clone().eval(env.clone(), platform)?;
+            Self::Update(u) => Code::Update(
+                u.update.compile(scopes),
+                compile_map(&u.set, scopes),
+                u.unset.clone(),
+            ),
 
-                match rec {
-                    Value::Record(r) => {
-                        let mut newrec: IndexMap<String, Value> = r
-                            .iter()
-                            .filter(|(k, _)| !u.unset.contains(*k))
-                            .map(|(k, v)| (k.clone(), v.clone()))
-                            .collect();
+            Self::PlatformCall(p) => {
+                Code::PlatformCall(p.platform.clone(), p.arg.compile(scopes), p.from.clone())
+            }
 
-                        for (k, v) in u.set {
-                            let val = v.eval(env.clone(), platform)?;
-                            newrec.insert(k, val);
-                        }
+            Self::Chain(c) => Code::Chain(
+                c.args
+                    .iter()
+                    .map(|e| Link {
+                        code: e.compile(scopes),
+                        name: e.name(),
+                        location: e.location().cloned(),
+                    })
+                    .collect(),
+            ),
+
+            Self::CaseOf(c) => Code::CaseOf(c.case.compile(scopes), Box::new(c.of.compile(scopes))),
+            Self::Import(i) => Code::Import(i.path.clone(), i.from.clone()),
+            Self::Native(n) => Code::Native(n.name.clone()),
+            Self::Fmt(f) => Code::Fmt(compile_template(&f.template, scopes)),
+            Self::Raise(r) => Code::Raise(r.raise.compile(scopes)),
+            Self::Try(t) => Code::Try(t.try_.compile(scopes), t.catch.compile(scopes)),
+        };
+        Arc::new(code)
+    }
 
-                        Ok(newrec.into())
-                    }
-                    _ => Err(Error::NotARecord(rec)),
-                }
+    /// Compile the value bound to a name, naming the function it makes, if
+    /// that's all it does.
+    pub(crate) fn compile_named(&self, name: &str, scopes: &mut Scopes) -> Arc<Code> {
+        match self {
+            Self::Lambda(l) => {
+                Arc::new(Code::Lambda(Arc::new(l.compile(Some(name.into()), scopes))))
             }
+            Self::Located(l) => Arc::new(Code::Located(
+                l.expr.compile_named(name, scopes),
+                l.location.clone(),
+            )),
+            e => e.compile(scopes),
+        }
+    }
+}
 
-            Expr::PlatformCall(p) => {
-                let env_ = env.clone();
-                platform.call(env, &p.platform, p.arg.eval(env_, platform)?)
-            }
+fn compile_all(exprs: &[Expr], scopes: &mut Scopes) -> Vec<Arc<Code>> {
+    exprs.iter().map(|e| e.compile(scopes)).collect()
+}
 
-            Self::Chain(c) => {
-                if let Some((target, fields)) = c.args.split_first() {
-                    let mut target = target.clone().eval(env.clone(), platform)?;
-                    for f in fields {
-                        if let Value::Function(f) = f.to_owned().eval(env.clone(), platform)? {
-                            target = f.call([Ok(target)], platform)?;
-                        }
-                    }
-                    Ok(target)
-                } else {
-                    Err(Error::NotEnoughArguments(".".into(), 2, c.args.len()))
-                }
-            }
+fn compile_map(items: &IndexMap<String, Expr>, scopes: &mut Scopes) -> Vec<(String, Arc<Code>)> {
+    items
+        .iter()
+        .map(|(k, v)| (k.clone(), v.compile_named(k, scopes)))
+        .collect()
+}
 
-            Expr::CaseOf(c) => {
-                let case = c.case.clone().eval(env.clone(), platform)?;
-
-                if let Ok(y) = yaml::to_value(&case) {
-                    if let Some(e) = c.of.exact.get(&y).cloned() {
-                        return e.eval(env, platform);
-                    }
-                };
-
-                if let Ok(v) = match &case {
-                    Value::Number(n) => {
-                        if n.is_i64() || n.is_u64() {
-                            c.of.integer
-                                .as_ref()
-                                .map(|l| {
-                                    env.insert(l.as_.clone(), case.clone().into());
-                                    l.do_.clone().eval(env.clone(), platform)
-                                })
-                                .unwrap_or_else(|| Err(Error::CaseError(n.clone().into())))
-                        } else {
-                            c.of.float
-                                .as_ref()
-                                .map(|l| {
-                                    env.insert(l.as_.clone(), case.clone().into());
-                                    l.do_.clone().eval(env.clone(), platform)
-                                })
-                                .unwrap_or_else(|| Err(Error::CaseError(n.clone().into())))
-                        }
-                    }
-
-                    Value::String(s) => c
-                        .of
-                        .string
-                        .map(|l| {
-                            let mut chars = s.chars();
-                            if let Some(c) = chars.next() {
-                                env.insert(l.as_.0, Expr::Value(c.to_string().into()));
-                                env.insert(l.as_.1, Expr::Value(chars.collect::<String>().into()));
-                                l.do_.eval(env.clone(), platform)
-                            } else {
-                                Err(Error::CaseError(s.clone().into()))
-                            }
-                        })
-                        .unwrap_or_else(|| Err(Error::CaseError(s.clone().into()))),
-
-                    Value::List(ref list) => {
-                        c.of.list
-                            .as_ref()
-                            .map(|l| {
-                                if let Some(item) = list.first() {
-                                    env.insert(l.as_.0.clone(), Expr::Value(item.clone()).into());
-                                    env.insert(
-                                        l.as_.1.clone(),
-                                        Expr::Value(
-                                            list.iter()
-                                                .skip(1)
-                                                .map(Clone::clone)
-                                                .collect::<Vec<Value>>()
-                                                .into(),
-                                        ),
-                                    );
-                                    l.do_.clone().eval(env.clone(), platform)
-                                } else {
-                                    Err(Error::CaseError(list.clone().into()))
-                                }
-                            })
-                            .unwrap_or_else(|| Err(Error::CaseError(case.clone().into())))
-                    }
-
-                    Value::Record(r) => c
-                        .of
-                        .record
-                        .as_ref()
-                        .map(|l| {
-                            for (k, v) in l.as_.clone().into_iter() {
-                                let f = v.eval(env.clone(), platform)?;
-                                if let Value::String(field) = f {
-                                    if let Ok(val) = Value::Record(r.clone()).get_from_yaml_nested(
-                                        field.split('.').map(RecordVal::de_field_name),
-                                    ) {
-                                        env.insert(k, val.clone().into());
-                                    }
-                                };
-                            }
-                            l.do_.clone().eval(env.clone(), platform)
-                        })
-                        .unwrap_or_else(|| Err(Error::CaseError(case.clone().into()))),
-
-                    Value::Function(f) => {
-                        c.of.function
-                            .as_ref()
-                            .map(|l| {
-                                env.insert(l.as_.clone(), case.clone().into());
-                                l.do_.clone().eval(env.clone(), platform)
-                            })
-                            .unwrap_or_else(|| Err(Error::CaseError(f.clone().into())))
-                    }
-                    Value::Null => {
-                        // Handled by exact match.
-                        Err(Error::CaseError(case.clone().into()))
-                    }
-                    Value::Bool(_) => {
-                        // Handled by exact match.
-                        Err(Error::CaseError(case.clone().into()))
-                    }
-                } {
-                    Ok(v)
-                } else {
-                    c.of.default
-                        .as_ref()
-                        .map(|l| {
-                            env.insert(l.as_.clone(), case.clone().into());
-                            l.do_.clone().eval(env, platform)
-                        })
-                        .unwrap_or_else(|| Err(Error::CaseError(case.clone().into())))
-                }
+fn compile_arm(names: Vec<String>, body: &Expr, scopes: &mut Scopes) -> Arm {
+    let names: Arc<[String]> = names.into();
+    let body = scopes.nested(Some(names.clone()), |scopes| body.compile(scopes));
+    Arm { names, body }
+}
+
+/// Split the template into text and placeholders. Anything that doesn't look
+/// like a placeholder, e.g. a `${` without a `}` or a name with spaces in it,
+/// is kept as text.
+fn compile_template(template: &str, scopes: &mut Scopes) -> Vec<Piece> {
+    let mut pieces = vec![];
+    let mut text = String::new();
+    let mut rest = template;
+
+    while let Some(i) = rest.find('$') {
+        text.push_str(&rest[..i]);
+        rest = &rest[i..];
+
+        let placeholder = rest
+            .strip_prefix("${")
+            .and_then(|r| r.split_once('}'))
+            .filter(|(name, _)| {
+                let name = name.trim();
+                !name.is_empty() && !name.contains(|c: char| c.is_whitespace() || "${".contains(c))
+            });
+
+        if let Some(r) = rest.strip_prefix("$$") {
+            text.push('$');
+            rest = r;
+        } else if let Some((name, r)) = placeholder {
+            if !text.is_empty() {
+                pieces.push(Piece::Text(std::mem::take(&mut text)));
             }
+            let name = Expr::Variable(name.trim().into());
+            pieces.push(Piece::Value(name.compile(scopes)));
+            rest = r;
+        } else {
+            text.push('$');
+            rest = &rest[1..];
         }
     }
+
+    text.push_str(rest);
+    if !text.is_empty() {
+        pieces.push(Piece::Text(text));
+    }
+    pieces
+}
+
+fn locate_opt(expr: Expr, node: Option<&Node>, origin: &Option<Arc<Origin>>) -> Expr {
+    if let Some(n) = node {
+        expr.locate(n, origin)
+    } else {
+        expr
+    }
+}
+
+fn locate_all(exprs: Vec<Expr>, nodes: &[Node], origin: &Option<Arc<Origin>>) -> Vec<Expr> {
+    exprs
+        .into_iter()
+        .enumerate()
+        .map(|(i, e)| locate_opt(e, nodes.get(i), origin))
+        .collect()
+}
+
+fn locate_map(
+    items: IndexMap<String, Expr>,
+    node: &Node,
+    origin: &Option<Arc<Origin>>,
+) -> IndexMap<String, Expr> {
+    items
+        .into_iter()
+        .map(|(k, v)| {
+            let v = locate_opt(v, node.get(&[&k]), origin);
+            (k, v)
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Located {
+    expr: Expr,
+    location: Location,
+}
+
+impl Located {
+    pub fn new(expr: Expr, location: Location) -> Self {
+        Self { expr, location }
+    }
+}
+
+impl Serialize for Located {
+    fn serialize<S>(&self, s: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.expr.serialize(s)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
@@ -458,11 +553,13 @@ impl Lambda {
     }
 
     pub fn to_function(self, env: Env) -> Function {
-        Function {
-            args: self.args,
-            env,
-            expr: self.do_,
-        }
+        Function::new(env, self.args, self.do_)
+    }
+
+    fn compile(&self, name: Option<String>, scopes: &mut Scopes) -> compile::Lambda {
+        let params: Arc<[String]> = self.args.clone().into();
+        let body = scopes.nested(Some(params.clone()), |scopes| self.do_.compile(scopes));
+        compile::Lambda { params, body, name }
     }
 }
 
@@ -498,14 +595,72 @@ pub struct PlatformCall {
     platform: String,
     #[serde(rename = ":arg")]
     arg: Expr,
+    /// The file making the call, whose capabilities it needs.
+    #[serde(skip)]
+    from: Option<Arc<Origin>>,
 }
 
 impl PlatformCall {
     pub fn new(platform: String, arg: Expr) -> Self {
-        Self { platform, arg }
+        Self {
+            platform,
+            arg,
+            from: None,
+        }
     }
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Import {
+    #[serde(rename = ":import")]
+    path: String,
+    /// The file doing the import, that relative paths are relative to.
+    #[serde(skip)]
+    from: Option<Arc<Origin>>,
+}
+
+impl Import {
+    pub fn new(path: String) -> Self {
+        Self { path, from: None }
+    }
+}
+
+/// A string with `${name}` placeholders, replaced by the values bound to the
+/// names. `$$` is a `$`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Fmt {
+    #[serde(rename = ":fmt")]
+    template: String,
+}
+
+/// A function implemented in Rust.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Native {
+    #[serde(rename = ":native")]
+    name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Raise {
+    #[serde(rename = ":raise")]
+    raise: Expr,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Try {
+    #[serde(rename = ":try")]
+    try_: Expr,
+
+    /// The function to call with the error, if there is one.
+    #[serde(rename = ":catch")]
+    catch: Expr,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
 #[serde(deny_unknown_fields)]
 pub struct Record {
@@ -546,7 +701,7 @@ pub struct List {
 #[serde(deny_unknown_fields)]
 pub struct LetIn {
     #[serde(rename = ":let")]
-    let_: Env,
+    let_: IndexMap<String, Expr>,
 
     #[serde(rename = ":in")]
     in_: Expr,
@@ -572,6 +727,41 @@ pub struct Add {
     args: Vec<Expr>,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Sub {
+    #[serde(rename = ":sub", alias = ":-")]
+    args: Vec<Expr>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Mul {
+    #[serde(rename = ":mul", alias = ":*")]
+    args: Vec<Expr>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Div {
+    #[serde(rename = ":div", alias = ":/")]
+    args: Vec<Expr>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Mod {
+    #[serde(rename = ":mod", alias = ":%")]
+    args: Vec<Expr>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Pow {
+    #[serde(rename = ":pow")]
+    args: Vec<Expr>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(deny_unknown_fields)]
 pub struct Append {
@@ -586,6 +776,48 @@ pub struct Equals {
     args: Vec<Expr>,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Lt {
+    #[serde(rename = ":lt", alias = ":<")]
+    args: Vec<Expr>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Le {
+    #[serde(rename = ":le", alias = ":<=")]
+    args: Vec<Expr>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Gt {
+    #[serde(rename = ":gt", alias = ":>")]
+    args: Vec<Expr>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Ge {
+    #[serde(rename = ":ge", alias = ":>=")]
+    args: Vec<Expr>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct And {
+    #[serde(rename = ":and", alias = ":&&")]
+    args: Vec<Expr>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Or {
+    #[serde(rename = ":or", alias = ":||")]
+    args: Vec<Expr>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(deny_unknown_fields)]
 pub struct Chain {
@@ -630,6 +862,81 @@ pub struct Matcher {
     default: Option<AsItem>,
 }
 
+impl Matcher {
+    fn compile(&self, scopes: &mut Scopes) -> Cases {
+        let item = |item: &Option<AsItem>, scopes: &mut Scopes| {
+            item.as_ref()
+                .map(|i| compile_arm(vec![i.as_.clone()], &i.do_, scopes))
+        };
+        let pair = |pair: &Option<AsPair>, scopes: &mut Scopes| {
+            pair.as_ref()
+                .map(|p| compile_arm(vec![p.as_.0.clone(), p.as_.1.clone()], &p.do_, scopes))
+        };
+
+        Cases {
+            exact: self
+                .exact
+                .iter()
+                .map(|(k, v)| (k.clone(), v.compile(scopes)))
+                .collect(),
+            integer: item(&self.integer, scopes),
+            float: item(&self.float, scopes),
+            string: pair(&self.string, scopes),
+            function: item(&self.function, scopes),
+            list: pair(&self.list, scopes),
+            record: self.record.as_ref().map(|r| {
+                let paths = r.as_.values().map(|v| v.compile(scopes)).collect();
+                let names = r.as_.keys().cloned().collect();
+                (paths, compile_arm(names, &r.do_, scopes))
+            }),
+            default: item(&self.default, scopes),
+        }
+    }
+
+    fn locate(mut self, node: &Node, origin: &Option<Arc<Origin>>) -> Self {
+        if let Some(n) = node.get(&[":eq", ":=="]) {
+            self.exact = self
+                .exact
+                .into_iter()
+                .zip(n.entries().iter().map(Some).chain(std::iter::repeat(None)))
+                .map(|((k, v), e)| (k, locate_opt(v, e.map(|(_, n)| n), origin)))
+                .collect();
+        }
+
+        let as_item = |item: Option<AsItem>, keys: &[&str]| {
+            item.map(|mut i| {
+                i.do_ = locate_opt(i.do_, node.get(keys).and_then(|n| n.get(&[":do"])), origin);
+                i
+            })
+        };
+        self.integer = as_item(self.integer, &[":int"]);
+        self.float = as_item(self.float, &[":float"]);
+        self.function = as_item(self.function, &[":function"]);
+        self.default = as_item(self.default, &[":default", ":_"]);
+
+        let as_pair = |pair: Option<AsPair>, keys: &[&str]| {
+            pair.map(|mut p| {
+                p.do_ = locate_opt(p.do_, node.get(keys).and_then(|n| n.get(&[":do"])), origin);
+                p
+            })
+        };
+        self.string = as_pair(self.string, &[":string"]);
+        self.list = as_pair(self.list, &[":list"]);
+
+        if let Some(mut r) = self.record.take() {
+            if let Some(n) = node.get(&[":rec"]) {
+                if let Some(a) = n.get(&[":as"]) {
+                    r.as_ = locate_map(r.as_, a, origin);
+                }
+                r.do_ = locate_opt(r.do_, n.get(&[":do"]), origin);
+            }
+            self.record = Some(r);
+        }
+
+        self
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(deny_unknown_fields)]
 pub struct AsItem {
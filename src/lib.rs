@@ -1,17 +1,28 @@
+mod compile;
 mod env;
 mod error;
 mod value;
 
 pub mod expr;
+pub mod loader;
+pub mod module;
+pub mod native;
 pub mod platform;
 pub mod vm;
 
+pub use compile::CompiledExpr;
 pub use env::Env;
-pub use error::{Error, Result};
+pub use error::{Error, Frame, Result};
 pub use expr::Expr;
-pub use platform::DefaultPlatform;
+pub use loader::{Loader, Location};
+pub use module::{Modules, Prelude};
+pub use native::{FromValue, Natives, Runtime};
+pub use platform::{Capabilities, DefaultPlatform, PlatformStack};
 pub use serde_yaml as yaml;
-pub use value::{Function, List, Record, Value};
+pub use value::{Dict, Function, List, Record, Value};
 pub use vm::Vm;
 pub use yaml::Number;
 pub use yaml::Value as Yaml;
+
+#[cfg(feature = "fs")]
+pub use platform::FsPlatform;
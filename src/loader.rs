@@ -0,0 +1,212 @@
+use crate::{yaml, Error, Expr, Result};
+use serde::de::Error as _;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust::scanner::Marker;
+
+/// Position of an expression in the YAML source it was loaded from.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Location {
+    pub file: Option<Arc<str>>,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}:{}:{}", file, self.line, self.column)
+        } else {
+            write!(f, "line {}, column {}", self.line, self.column)
+        }
+    }
+}
+
+/// The file some code was loaded from, and the files with capabilities of
+/// their own that imported it, directly or not, which limit what it can do
+/// too.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Origin {
+    pub(crate) file: Arc<str>,
+    pub(crate) importers: Vec<Arc<str>>,
+}
+
+impl Origin {
+    /// The file, and the files that imported it.
+    pub(crate) fn files(&self) -> impl Iterator<Item = &Arc<str>> {
+        std::iter::once(&self.file).chain(&self.importers)
+    }
+}
+
+/// Loads yamlfun code, remembering where every expression came from so that
+/// runtime errors can point back to the source.
+#[derive(Debug, Clone, Default)]
+pub struct Loader {
+    origin: Option<Arc<Origin>>,
+}
+
+impl Loader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_file<S>(mut self, file: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.origin = Some(Arc::new(Origin {
+            file: file.into().into(),
+            importers: vec![],
+        }));
+        self
+    }
+
+    pub(crate) fn with_origin(mut self, origin: Origin) -> Self {
+        self.origin = Some(Arc::new(origin));
+        self
+    }
+
+    pub fn load(&self, code: &str) -> Result<Expr> {
+        let expr: Expr = yaml::from_str(code)?;
+
+        let mut builder = Builder::default();
+        Parser::new(code.chars())
+            .load(&mut builder, false)
+            .map_err(|e| Error::YamlError(yaml::Error::custom(e)))?;
+
+        if let Some(node) = builder.root {
+            Ok(expr.locate(&node, &self.origin))
+        } else {
+            Ok(expr)
+        }
+    }
+}
+
+pub fn from_str(code: &str) -> Result<Expr> {
+    Loader::new().load(code)
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum Node {
+    Scalar(Marker),
+    Sequence(Vec<Node>, Marker),
+    Mapping(Vec<(String, Node)>, Marker),
+}
+
+impl Node {
+    fn mark(&self) -> &Marker {
+        match self {
+            Self::Scalar(m) | Self::Sequence(_, m) | Self::Mapping(_, m) => m,
+        }
+    }
+
+    pub(crate) fn location(&self, origin: &Option<Arc<Origin>>) -> Location {
+        let mark = self.mark();
+        Location {
+            file: origin.as_ref().map(|o| o.file.clone()),
+            line: mark.line(),
+            column: mark.col() + 1,
+        }
+    }
+
+    pub(crate) fn get(&self, keys: &[&str]) -> Option<&Node> {
+        match self {
+            Self::Mapping(entries, _) => entries
+                .iter()
+                .find(|(k, _)| keys.contains(&k.as_str()))
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn items(&self) -> &[Node] {
+        match self {
+            Self::Sequence(items, _) => items,
+            _ => &[],
+        }
+    }
+
+    pub(crate) fn entries(&self) -> &[(String, Node)] {
+        match self {
+            Self::Mapping(entries, _) => entries,
+            _ => &[],
+        }
+    }
+}
+
+enum Partial {
+    Sequence(Vec<Node>, Marker, usize),
+    Mapping(Vec<(String, Node)>, Option<String>, Marker, usize),
+}
+
+#[derive(Default)]
+struct Builder {
+    stack: Vec<Partial>,
+    anchors: HashMap<usize, Node>,
+    root: Option<Node>,
+}
+
+impl Builder {
+    fn finish(&mut self, node: Node, anchor: usize) {
+        if anchor > 0 {
+            self.anchors.insert(anchor, node.clone());
+        }
+        self.push(node, None);
+    }
+
+    fn push(&mut self, node: Node, scalar: Option<String>) {
+        match self.stack.last_mut() {
+            Some(Partial::Sequence(items, _, _)) => items.push(node),
+            Some(Partial::Mapping(entries, key, mark, _)) => {
+                if let Some(k) = key.take() {
+                    entries.push((k, node));
+                } else {
+                    // Block mappings are marked after their first key.
+                    if entries.is_empty() && node.mark().index() < mark.index() {
+                        *mark = *node.mark();
+                    }
+                    *key = Some(scalar.unwrap_or_default());
+                }
+            }
+            None => self.root = Some(node),
+        }
+    }
+}
+
+impl MarkedEventReceiver for Builder {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        match ev {
+            Event::Scalar(value, _, anchor, _) => {
+                let node = Node::Scalar(mark);
+                if anchor > 0 {
+                    self.anchors.insert(anchor, node.clone());
+                }
+                self.push(node, Some(value));
+            }
+            Event::Alias(anchor) => {
+                if let Some(node) = self.anchors.get(&anchor).cloned() {
+                    self.push(node, None);
+                }
+            }
+            Event::SequenceStart(anchor) => {
+                self.stack.push(Partial::Sequence(vec![], mark, anchor));
+            }
+            Event::MappingStart(anchor) => {
+                self.stack
+                    .push(Partial::Mapping(vec![], None, mark, anchor));
+            }
+            Event::SequenceEnd | Event::MappingEnd => match self.stack.pop() {
+                Some(Partial::Sequence(items, mark, anchor)) => {
+                    self.finish(Node::Sequence(items, mark), anchor)
+                }
+                Some(Partial::Mapping(entries, _, mark, anchor)) => {
+                    self.finish(Node::Mapping(entries, mark), anchor)
+                }
+                None => {}
+            },
+            _ => {}
+        }
+    }
+}
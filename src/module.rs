@@ -0,0 +1,333 @@
+use crate::compile::{self, Scope};
+use crate::env::Thunk;
+use crate::loader::Origin;
+use crate::platform::Platform;
+use crate::vm::Context;
+use crate::{Env, Error, Expr, Loader, Result, Value};
+use indexmap::IndexMap;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// The modules a VM knows about, by name, and where to look for the ones in
+/// files.
+///
+/// A module is loaded the first time it's used, and only once. Modules can
+/// use each other by name.
+#[derive(Debug, Clone, Default)]
+pub struct Modules {
+    modules: IndexMap<String, Module>,
+    search_paths: Vec<PathBuf>,
+    /// The modules imported from files.
+    files: Arc<Mutex<HashMap<FileKey, Thunk>>>,
+}
+
+/// The canonical path of a file, and the files with capabilities of their own
+/// that imported it.
+type FileKey = (PathBuf, Vec<Arc<str>>);
+
+#[derive(Clone)]
+struct Module(Arc<Inner>);
+
+struct Inner {
+    file: String,
+    source: Cow<'static, str>,
+    thunk: OnceLock<Thunk>,
+}
+
+impl Modules {
+    /// No modules at all.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The standard library.
+    pub fn std() -> Self {
+        Self::new()
+            .with("Basics", include_str!("./Yaml/Basics.yaml"))
+            .with("Maybe", include_str!("./Yaml/Maybe.yaml"))
+            .with("List", include_str!("./Yaml/List.yaml"))
+            .with("Rec", include_str!("./Yaml/Rec.yaml"))
+            .with("Result", include_str!("./Yaml/Result.yaml"))
+            .with("String", include_str!("./Yaml/String.yaml"))
+            .with("Dict", include_str!("./Yaml/Dict.yaml"))
+            .with("Std", include_str!("./Yaml/Std.yaml"))
+    }
+
+    /// Add a module, replacing any module with the same name.
+    pub fn with<S, T>(mut self, name: S, source: T) -> Self
+    where
+        S: Into<String>,
+        T: Into<Cow<'static, str>>,
+    {
+        self.insert(name.into(), source.into());
+        self
+    }
+
+    pub fn insert(&mut self, name: String, source: Cow<'static, str>) {
+        let module = Module(Arc::new(Inner {
+            file: format!("{}.yaml", name),
+            source,
+            thunk: OnceLock::new(),
+        }));
+        self.modules.insert(name, module);
+    }
+
+    /// Add a directory to look for imported files in, after the ones
+    /// already added.
+    pub fn with_search_path<S>(mut self, path: S) -> Self
+    where
+        S: Into<PathBuf>,
+    {
+        self.search_paths.push(path.into());
+        self
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.modules.contains_key(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.modules.keys()
+    }
+
+    /// The value of the module, loading it if it's the first time it's used.
+    pub fn load<P: Platform>(&self, name: &str, ctx: &Context<P>) -> Result<Value> {
+        let module = self
+            .modules
+            .get(name)
+            .ok_or_else(|| Error::Undefined(name.into()))?;
+
+        let thunk = match module.0.thunk.get() {
+            Some(thunk) => thunk.clone(),
+            None => {
+                let expr = Loader::new()
+                    .with_file(module.0.file.as_str())
+                    .load(&module.0.source)?;
+                let thunk = Thunk::new(name.into(), compile::compile(&expr));
+                thunk.bind(Scope::new(self.env()));
+                module.0.thunk.get_or_init(|| thunk).clone()
+            }
+        };
+
+        thunk.force(ctx)
+    }
+
+    /// The value of the module at `path`: either one added by name, or a
+    /// file. Paths starting with `./` or `../` are relative to the file
+    /// importing them, other relative paths are looked for in the search
+    /// paths.
+    ///
+    /// Only files in the directory of the file importing them, or in a search
+    /// path, can be imported, and only by code with the `:import`
+    /// capability.
+    ///
+    /// A file only sees the names every script starts with, and the modules
+    /// by name, not those of the code importing it, since it's loaded once
+    /// for all of them. Once for each set of files with capabilities of their
+    /// own that imported it, that is, since it can't do more than any of
+    /// them.
+    pub(crate) fn import<P: Platform>(
+        &self,
+        path: &str,
+        from: Option<&Origin>,
+        ctx: &Context<P>,
+    ) -> Result<Value> {
+        if self.contains(path) {
+            return self.load(path, ctx);
+        }
+
+        ctx.allow(":import", from)?;
+        let file = self
+            .resolve(path, from.map(|o| &*o.file))
+            .ok_or_else(|| Error::ModuleNotFound(path.into()))?;
+        let name = file.display().to_string();
+
+        let mut importers: Vec<Arc<str>> = vec![];
+        for f in from.into_iter().flat_map(Origin::files) {
+            if ctx.config.module_capabilities.contains_key(&**f) && !importers.contains(f) {
+                importers.push(f.clone());
+            }
+        }
+
+        let thunk = {
+            let mut files = self.files.lock().unwrap_or_else(|e| e.into_inner());
+            let key = (file, importers);
+            match files.get(&key) {
+                Some(thunk) => thunk.clone(),
+                None => {
+                    let source =
+                        fs::read_to_string(&key.0).map_err(|e| Error::IoError(name.clone(), e))?;
+                    let origin = Origin {
+                        file: name.as_str().into(),
+                        importers: key.1.clone(),
+                    };
+                    let expr = Loader::new().with_origin(origin).load(&source)?;
+                    let thunk = Thunk::new(name.clone(), compile::compile(&expr));
+                    thunk.bind(Scope::new(self.base_env(&ctx.env)));
+                    files.insert(key, thunk.clone());
+                    thunk
+                }
+            }
+        };
+
+        thunk.force(ctx).map_err(|e| match e {
+            Error::CyclicBinding(n) if n == name => Error::CyclicImport(n),
+            e => e,
+        })
+    }
+
+    /// The file name the code of a module added by name is from.
+    pub(crate) fn file(&self, name: &str) -> Option<&str> {
+        self.modules.get(name).map(|m| m.0.file.as_str())
+    }
+
+    /// The canonical path of the file to import, if it exists, and is in the
+    /// directory of the file importing it or in a search path. Code that
+    /// isn't in a file, or is in a module added by name, has no directory.
+    pub(crate) fn resolve(&self, path: &str, from: Option<&str>) -> Option<PathBuf> {
+        let dir = from
+            .filter(|f| !self.modules.values().any(|m| m.0.file == *f))
+            .map(|f| match Path::new(f).parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => dir,
+                _ => Path::new("."),
+            });
+
+        let candidates = if path.starts_with("./") || path.starts_with("../") {
+            vec![dir.unwrap_or_else(|| Path::new(".")).join(path)]
+        } else if Path::new(path).is_absolute() {
+            vec![PathBuf::from(path)]
+        } else {
+            self.search_paths.iter().map(|dir| dir.join(path)).collect()
+        };
+
+        let roots: Vec<PathBuf> = dir
+            .into_iter()
+            .chain(self.search_paths.iter().map(PathBuf::as_path))
+            .filter_map(|dir| dir.canonicalize().ok())
+            .collect();
+
+        candidates
+            .into_iter()
+            .filter_map(|p| p.canonicalize().ok())
+            .find(|p| roots.iter().any(|root| p.starts_with(root)))
+    }
+
+    /// `env`, with every module it doesn't shadow bound to its name.
+    fn base_env(&self, env: &Env) -> Env {
+        let mut env = env.clone();
+        let modules = self
+            .names()
+            .filter(|name| !env.contains_key(name))
+            .map(|name| (name.clone(), Expr::Module(name.clone())))
+            .collect::<Vec<_>>();
+        env.extend(modules);
+        env
+    }
+
+    /// Every module, bound to its name.
+    fn env(&self) -> Env {
+        self.names()
+            .map(|name| (name.clone(), Expr::Module(name.clone())))
+            .collect()
+    }
+}
+
+impl fmt::Debug for Module {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Module").field(&self.0.file).finish()
+    }
+}
+
+/// The names every script can use without the name of the module they come
+/// from.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Prelude {
+    names: IndexMap<String, String>,
+}
+
+impl Prelude {
+    /// No names at all.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The standard prelude: the modules of the standard library, and the
+    /// basic functions.
+    pub fn std() -> Self {
+        STD.iter()
+            .fold(Self::new(), |p, (name, path)| p.with(*name, *path))
+    }
+
+    /// Make `name` refer to `path`, a module or a field of one, like
+    /// `Basics.add`.
+    pub fn with<S, T>(mut self, name: S, path: T) -> Self
+    where
+        S: Into<String>,
+        T: Into<String>,
+    {
+        self.names.insert(name.into(), path.into());
+        self
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.names.iter()
+    }
+}
+
+const STD: &[(&str, &str)] = &[
+    ("Std", "Std"),
+    ("Basics", "Basics"),
+    ("Maybe", "Maybe"),
+    ("List", "List"),
+    ("Rec", "Rec"),
+    ("Result", "Result"),
+    ("String", "String"),
+    ("Dict", "Dict"),
+    ("null_", "Basics.null_"),
+    ("Bool", "Basics.Bool"),
+    ("true_", "Basics.Bool.true_"),
+    ("false_", "Basics.Bool.false_"),
+    ("add", "Basics.add"),
+    ("(+)", "Basics.add"),
+    ("sub", "Basics.sub"),
+    ("(-)", "Basics.sub"),
+    ("mul", "Basics.mul"),
+    ("(*)", "Basics.mul"),
+    ("div", "Basics.div"),
+    ("(/)", "Basics.div"),
+    ("mod", "Basics.mod"),
+    ("(%)", "Basics.mod"),
+    ("pow", "Basics.pow"),
+    ("(^)", "Basics.pow"),
+    ("not", "Basics.not"),
+    ("(!)", "Basics.not"),
+    ("eq", "Basics.eq"),
+    ("(==)", "Basics.eq"),
+    ("ne", "Basics.ne"),
+    ("(!=)", "Basics.ne"),
+    ("lt", "Basics.lt"),
+    ("(<)", "Basics.lt"),
+    ("le", "Basics.le"),
+    ("(<=)", "Basics.le"),
+    ("gt", "Basics.gt"),
+    ("(>)", "Basics.gt"),
+    ("ge", "Basics.ge"),
+    ("(>=)", "Basics.ge"),
+    ("compare", "Basics.compare"),
+    ("max", "Basics.max"),
+    ("min", "Basics.min"),
+    ("clamp", "Basics.clamp"),
+    ("composeL", "Basics.composeL"),
+    ("(<<)", "Basics.composeL"),
+    ("composeR", "Basics.composeR"),
+    ("(>>)", "Basics.composeR"),
+    ("and", "Basics.and"),
+    ("(&&)", "Basics.and"),
+    ("or", "Basics.or"),
+    ("(||)", "Basics.or"),
+    ("xor", "Basics.xor"),
+];
@@ -0,0 +1,156 @@
+use crate::{Dict, Error, Function, List, Number, Record, Result, Value};
+
+/// A type arguments of native functions can be converted to.
+pub trait FromValue: Sized {
+    /// `None` if the value isn't of this type.
+    fn from_value(value: Value) -> Option<Self>;
+}
+
+impl FromValue for Value {
+    fn from_value(value: Value) -> Option<Self> {
+        Some(value)
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::Bool(b) => Some(b),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for Number {
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::Number(n) => Some(n),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for i64 {
+    fn from_value(value: Value) -> Option<Self> {
+        Number::from_value(value)?.as_i64()
+    }
+}
+
+impl FromValue for u64 {
+    fn from_value(value: Value) -> Option<Self> {
+        Number::from_value(value)?.as_u64()
+    }
+}
+
+impl FromValue for f64 {
+    fn from_value(value: Value) -> Option<Self> {
+        Number::from_value(value)?.as_f64()
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for List {
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::List(l) => Some(l),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for Record {
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::Record(r) => Some(r),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for Dict {
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::Dict(d) => Some(d),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for Function {
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::Function(f) => Some(*f),
+            _ => None,
+        }
+    }
+}
+
+/// `null` is `None`.
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::Null => Some(None),
+            v => T::from_value(v).map(Some),
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: Value) -> Option<Self> {
+        List::from_value(value)?
+            .iter()
+            .cloned()
+            .map(T::from_value)
+            .collect()
+    }
+}
+
+/// A Rust function that can be called from yamlfun, taking arguments of
+/// types in `Args`.
+pub trait IntoNative<Args>: Send + Sync + 'static {
+    /// The names of the arguments, one per argument.
+    fn params() -> Vec<String>;
+
+    /// Call the function with the arguments converted, or fail with
+    /// `Error::InvalidArguments` if they can't be.
+    fn call(&self, name: &str, args: Vec<Value>) -> Result<Value>;
+}
+
+macro_rules! into_native {
+    ($($arg:ident $var:ident),+) => {
+        impl<F, R, $($arg),+> IntoNative<($($arg,)+)> for F
+        where
+            F: Fn($($arg),+) -> Result<R> + Send + Sync + 'static,
+            R: Into<Value>,
+            $($arg: FromValue),+
+        {
+            fn params() -> Vec<String> {
+                [$(stringify!($var)),+].iter().map(|p| p.to_string()).collect()
+            }
+
+            fn call(&self, name: &str, args: Vec<Value>) -> Result<Value> {
+                let mut values = args.iter().cloned();
+                let converted = (|| Some(($($arg::from_value(values.next()?)?,)+)))();
+                match converted {
+                    Some(($($var,)+)) => self($($var),+).map(Into::into),
+                    None => Err(Error::InvalidArguments(name.into(), args)),
+                }
+            }
+        }
+    };
+}
+
+into_native!(A a);
+into_native!(A a, B b);
+into_native!(A a, B b, C c);
+into_native!(A a, B b, C c, D d);
+into_native!(A a, B b, C c, D d, E e);
+into_native!(A a, B b, C c, D d, E e, G g);
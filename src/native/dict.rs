@@ -0,0 +1,128 @@
+use super::{Natives, Runtime};
+use crate::{Dict, Error, Number, Result, Value};
+use std::convert::TryFrom;
+
+pub(super) fn add(natives: Natives) -> Natives {
+    natives
+        .with("Dict.insert", &["key", "value", "dict"], insert)
+        .with("Dict.get", &["key", "dict"], get)
+        .with("Dict.remove", &["key", "dict"], remove)
+        .with("Dict.member", &["key", "dict"], member)
+        .with("Dict.size", &["dict"], size)
+        .with("Dict.keys", &["dict"], keys)
+        .with("Dict.values", &["dict"], values)
+        .with("Dict.toList", &["dict"], to_list)
+        .with("Dict.fromList", &["list"], from_list)
+        .with("Dict.fromRecord", &["record"], from_record)
+        .with("Dict.merge", &["dict1", "dict2"], merge)
+}
+
+fn insert(args: Vec<Value>, _: &dyn Runtime) -> Result<Value> {
+    match &args[..] {
+        [key, value, Value::Dict(dict)] => {
+            let mut dict = dict.clone();
+            dict.insert(key.clone(), value.clone())?;
+            Ok(dict.into())
+        }
+        _ => Err(Error::InvalidArguments("Dict.insert".into(), args)),
+    }
+}
+
+/// The value as a list of one, or an empty list if there's none.
+fn get(args: Vec<Value>, _: &dyn Runtime) -> Result<Value> {
+    match &args[..] {
+        [key, Value::Dict(dict)] => Ok(dict
+            .get(key)
+            .cloned()
+            .into_iter()
+            .collect::<Vec<Value>>()
+            .into()),
+        _ => Err(Error::InvalidArguments("Dict.get".into(), args)),
+    }
+}
+
+fn remove(args: Vec<Value>, _: &dyn Runtime) -> Result<Value> {
+    match &args[..] {
+        [key, Value::Dict(dict)] => {
+            let mut dict = dict.clone();
+            dict.remove(key);
+            Ok(dict.into())
+        }
+        _ => Err(Error::InvalidArguments("Dict.remove".into(), args)),
+    }
+}
+
+fn member(args: Vec<Value>, _: &dyn Runtime) -> Result<Value> {
+    match &args[..] {
+        [key, Value::Dict(dict)] => Ok(Value::Bool(dict.contains_key(key))),
+        _ => Err(Error::InvalidArguments("Dict.member".into(), args)),
+    }
+}
+
+fn size(args: Vec<Value>, _: &dyn Runtime) -> Result<Value> {
+    match &args[..] {
+        [Value::Dict(dict)] => Ok(Number::from(dict.len() as u64).into()),
+        _ => Err(Error::InvalidArguments("Dict.size".into(), args)),
+    }
+}
+
+fn keys(args: Vec<Value>, _: &dyn Runtime) -> Result<Value> {
+    match &args[..] {
+        [Value::Dict(dict)] => Ok(dict.keys().cloned().collect::<Vec<Value>>().into()),
+        _ => Err(Error::InvalidArguments("Dict.keys".into(), args)),
+    }
+}
+
+fn values(args: Vec<Value>, _: &dyn Runtime) -> Result<Value> {
+    match &args[..] {
+        [Value::Dict(dict)] => Ok(dict.values().cloned().collect::<Vec<Value>>().into()),
+        _ => Err(Error::InvalidArguments("Dict.values".into(), args)),
+    }
+}
+
+fn to_list(args: Vec<Value>, _: &dyn Runtime) -> Result<Value> {
+    match &args[..] {
+        [Value::Dict(dict)] => Ok(dict
+            .iter()
+            .map(|(k, v)| vec![k.clone(), v.clone()].into())
+            .collect::<Vec<Value>>()
+            .into()),
+        _ => Err(Error::InvalidArguments("Dict.toList".into(), args)),
+    }
+}
+
+fn from_list(args: Vec<Value>, _: &dyn Runtime) -> Result<Value> {
+    if let [Value::List(list)] = &args[..] {
+        let mut dict = Dict::new();
+        for entry in list.iter() {
+            match entry {
+                Value::List(pair) if pair.len() == 2 => {
+                    dict.insert(pair[0].clone(), pair[1].clone())?;
+                }
+                _ => return Err(Error::InvalidArguments("Dict.fromList".into(), args)),
+            }
+        }
+        return Ok(dict.into());
+    }
+    Err(Error::InvalidArguments("Dict.fromList".into(), args))
+}
+
+fn from_record(args: Vec<Value>, _: &dyn Runtime) -> Result<Value> {
+    match &args[..] {
+        [Value::Record(record)] => Ok(Dict::try_from(record.clone())?.into()),
+        _ => Err(Error::InvalidArguments("Dict.fromRecord".into(), args)),
+    }
+}
+
+fn merge(args: Vec<Value>, _: &dyn Runtime) -> Result<Value> {
+    match &args[..] {
+        [Value::Dict(dict1), Value::Dict(dict2)] => {
+            let mut dict = dict1.clone();
+            for (k, v) in dict2.iter() {
+                dict.insert(k.clone(), v.clone())?;
+            }
+            Ok(dict.into())
+        }
+        _ => Err(Error::InvalidArguments("Dict.merge".into(), args)),
+    }
+}
@@ -0,0 +1,148 @@
+use super::{Natives, Runtime};
+use crate::{Error, Number, Result, Value};
+use std::cmp::Ordering;
+
+pub(super) fn add(natives: Natives) -> Natives {
+    natives
+        .with("List.map", &["f", "list"], map)
+        .with("List.filter", &["f", "list"], filter)
+        .with("List.foldl", &["f", "acc", "list"], foldl)
+        .with("List.foldr", &["f", "acc", "list"], foldr)
+        .with("List.length", &["list"], length)
+        .with("List.reverse", &["list"], reverse)
+        .with("List.range", &["from", "to"], range)
+        .with("List.zip", &["list1", "list2"], zip)
+        .with("List.sort", &["list"], sort)
+        .with("List.sortBy", &["f", "list"], sort_by)
+}
+
+fn map(args: Vec<Value>, rt: &dyn Runtime) -> Result<Value> {
+    match &args[..] {
+        [Value::Function(f), Value::List(list)] => list
+            .iter()
+            .map(|v| rt.call(f, vec![v.clone()]))
+            .collect::<Result<Vec<Value>>>()
+            .map(Value::from),
+        _ => Err(Error::InvalidArguments("List.map".into(), args)),
+    }
+}
+
+fn filter(args: Vec<Value>, rt: &dyn Runtime) -> Result<Value> {
+    match &args[..] {
+        [Value::Function(f), Value::List(list)] => {
+            let mut items = vec![];
+            for v in list.iter() {
+                match rt.call(f, vec![v.clone()])? {
+                    Value::Bool(true) => items.push(v.clone()),
+                    Value::Bool(false) => {}
+                    v => return Err(Error::NotABoolean(v)),
+                }
+            }
+            Ok(items.into())
+        }
+        _ => Err(Error::InvalidArguments("List.filter".into(), args)),
+    }
+}
+
+fn foldl(args: Vec<Value>, rt: &dyn Runtime) -> Result<Value> {
+    match &args[..] {
+        [Value::Function(f), acc, Value::List(list)] => list
+            .iter()
+            .try_fold(acc.clone(), |acc, v| rt.call(f, vec![v.clone(), acc])),
+        _ => Err(Error::InvalidArguments("List.foldl".into(), args)),
+    }
+}
+
+fn foldr(args: Vec<Value>, rt: &dyn Runtime) -> Result<Value> {
+    match &args[..] {
+        [Value::Function(f), acc, Value::List(list)] => list
+            .iter()
+            .rev()
+            .try_fold(acc.clone(), |acc, v| rt.call(f, vec![v.clone(), acc])),
+        _ => Err(Error::InvalidArguments("List.foldr".into(), args)),
+    }
+}
+
+fn length(args: Vec<Value>, _: &dyn Runtime) -> Result<Value> {
+    match &args[..] {
+        [Value::List(list)] => Ok(Number::from(list.len() as u64).into()),
+        _ => Err(Error::InvalidArguments("List.length".into(), args)),
+    }
+}
+
+fn reverse(args: Vec<Value>, _: &dyn Runtime) -> Result<Value> {
+    match &args[..] {
+        [Value::List(list)] => Ok(list.iter().rev().cloned().collect::<Vec<Value>>().into()),
+        _ => Err(Error::InvalidArguments("List.reverse".into(), args)),
+    }
+}
+
+fn range(args: Vec<Value>, rt: &dyn Runtime) -> Result<Value> {
+    let bounds = match &args[..] {
+        [Value::Number(from), Value::Number(to)] => from.as_i64().zip(to.as_i64()),
+        _ => None,
+    };
+    match bounds {
+        Some((from, to)) => {
+            let mut items = vec![];
+            for i in from..=to {
+                rt.step()?;
+                items.push(Number::from(i).into());
+            }
+            Ok(items.into())
+        }
+        None => Err(Error::InvalidArguments("List.range".into(), args)),
+    }
+}
+
+fn zip(args: Vec<Value>, _: &dyn Runtime) -> Result<Value> {
+    match &args[..] {
+        [Value::List(list1), Value::List(list2)] => Ok(list1
+            .iter()
+            .zip(list2.iter())
+            .map(|(a, b)| vec![a.clone(), b.clone()].into())
+            .collect::<Vec<Value>>()
+            .into()),
+        _ => Err(Error::InvalidArguments("List.zip".into(), args)),
+    }
+}
+
+fn sort(args: Vec<Value>, _: &dyn Runtime) -> Result<Value> {
+    match &args[..] {
+        [Value::List(list)] => sorted("List.sort", list.to_vec(), list.to_vec()),
+        _ => Err(Error::InvalidArguments("List.sort".into(), args)),
+    }
+}
+
+fn sort_by(args: Vec<Value>, rt: &dyn Runtime) -> Result<Value> {
+    match &args[..] {
+        [Value::Function(f), Value::List(list)] => {
+            let keys = list
+                .iter()
+                .map(|v| rt.call(f, vec![v.clone()]))
+                .collect::<Result<Vec<Value>>>()?;
+            sorted("List.sortBy", keys, list.to_vec())
+        }
+        _ => Err(Error::InvalidArguments("List.sortBy".into(), args)),
+    }
+}
+
+/// Sort the items by their keys, keeping the order of items with equal keys.
+fn sorted(name: &str, keys: Vec<Value>, items: Vec<Value>) -> Result<Value> {
+    let mut pairs: Vec<(Value, Value)> = keys.into_iter().zip(items).collect();
+    let mut incomparable = None;
+    pairs.sort_by(|(a, _), (b, _)| {
+        a.compare(b).unwrap_or_else(|| {
+            incomparable.get_or_insert_with(|| vec![a.clone(), b.clone()]);
+            Ordering::Equal
+        })
+    });
+    match incomparable {
+        Some(args) => Err(Error::InvalidArguments(name.into(), args)),
+        None => Ok(pairs
+            .into_iter()
+            .map(|(_, v)| v)
+            .collect::<Vec<Value>>()
+            .into()),
+    }
+}
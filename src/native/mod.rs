@@ -0,0 +1,153 @@
+//! Functions implemented in Rust, for the parts of the standard library that
+//! would be too slow in yamlfun.
+
+mod convert;
+mod dict;
+mod list;
+mod string;
+
+use crate::compile::{self, Code, Lambda, Scope};
+use crate::platform::Platform;
+use crate::vm::{Config, Context};
+use crate::{Env, Expr, Function, Result, Value};
+use indexmap::IndexMap;
+use std::fmt;
+use std::sync::Arc;
+
+pub use convert::{FromValue, IntoNative};
+
+/// What a native function or a platform call can do with the evaluation
+/// calling it. Everything it does counts towards the same limits.
+pub trait Runtime {
+    /// Call the function with all of its arguments.
+    ///
+    /// Errors come back wrapped with where they happened and the calls in
+    /// progress, like `Error::Located`, so match on `Error::root` to tell
+    /// what went wrong.
+    fn call(&self, func: &Function, args: Vec<Value>) -> Result<Value>;
+
+    /// Evaluate an expression with the given bindings.
+    fn eval(&self, expr: &Expr, env: Env) -> Result<Value>;
+
+    /// Count a step of work towards the VM's limits.
+    fn step(&self) -> Result<()>;
+
+    /// Number of evaluation steps taken so far.
+    fn steps(&self) -> u64;
+
+    /// The VM's limits, and how it does arithmetic.
+    fn config(&self) -> &Config;
+}
+
+impl<P: Platform> Runtime for Context<'_, P> {
+    fn call(&self, func: &Function, args: Vec<Value>) -> Result<Value> {
+        // A call back from a native function takes about twice the stack of
+        // a nested evaluation, so it counts as two levels of depth.
+        self.enter()?;
+        let res = func
+            .clone()
+            .enter(args)
+            .and_then(|next| compile::run(next, self));
+        self.leave();
+        res
+    }
+
+    fn eval(&self, expr: &Expr, env: Env) -> Result<Value> {
+        expr.clone().eval(env, self)
+    }
+
+    fn step(&self) -> Result<()> {
+        Context::step(self)
+    }
+
+    fn steps(&self) -> u64 {
+        Context::steps(self)
+    }
+
+    fn config(&self) -> &Config {
+        self.config
+    }
+}
+
+type NativeFn = dyn Fn(Vec<Value>, &dyn Runtime) -> Result<Value> + Send + Sync;
+
+/// The body of a native function.
+#[derive(Clone)]
+pub(crate) struct Native(Arc<NativeFn>);
+
+impl Native {
+    pub(crate) fn call(&self, args: Vec<Value>, rt: &dyn Runtime) -> Result<Value> {
+        (self.0)(args, rt)
+    }
+}
+
+impl fmt::Debug for Native {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Native")
+    }
+}
+
+/// The native functions a VM knows about, by the name `:native` refers to
+/// them with.
+#[derive(Clone, Default)]
+pub struct Natives {
+    natives: IndexMap<String, Function>,
+}
+
+impl Natives {
+    /// No native functions at all.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The ones the standard library needs.
+    pub fn std() -> Self {
+        dict::add(string::add(list::add(Self::new())))
+    }
+
+    /// Add a function taking arguments with the given names, replacing any
+    /// function with the same name.
+    pub fn with<S, F>(mut self, name: S, params: &[&str], f: F) -> Self
+    where
+        S: Into<String>,
+        F: Fn(Vec<Value>, &dyn Runtime) -> Result<Value> + Send + Sync + 'static,
+    {
+        let name = name.into();
+        let lambda = Lambda {
+            params: params.iter().map(|p| p.to_string()).collect(),
+            body: Arc::new(Code::NativeBody(Native(Arc::new(f)))),
+            name: Some(name.clone()),
+        };
+        let func = Function::from_lambda(Arc::new(lambda), Scope::default());
+        self.natives.insert(name, func);
+        self
+    }
+
+    /// Add a Rust function, replacing any function with the same name. It
+    /// takes as many arguments as `f` does, converted with `FromValue`.
+    pub fn with_fn<S, F, A>(self, name: S, f: F) -> Self
+    where
+        S: Into<String>,
+        F: IntoNative<A>,
+    {
+        let name = name.into();
+        let params = F::params();
+        let params: Vec<&str> = params.iter().map(String::as_str).collect();
+        let fname = name.clone();
+        self.with(name, &params, move |args, _| f.call(&fname, args))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Function> {
+        self.natives.get(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.natives.keys()
+    }
+}
+
+impl fmt::Debug for Natives {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.names()).finish()
+    }
+}
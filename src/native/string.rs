@@ -0,0 +1,164 @@
+use super::{Natives, Runtime};
+use crate::{Error, Number, Result, Value};
+
+pub(super) fn add(natives: Natives) -> Natives {
+    natives
+        .with("String.length", &["string"], length)
+        .with("String.slice", &["start", "end", "string"], slice)
+        .with("String.split", &["separator", "string"], split)
+        .with("String.join", &["separator", "strings"], join)
+        .with("String.trim", &["string"], trim)
+        .with("String.toUpper", &["string"], to_upper)
+        .with("String.toLower", &["string"], to_lower)
+        .with("String.contains", &["part", "string"], contains)
+        .with("String.startsWith", &["prefix", "string"], starts_with)
+        .with("String.endsWith", &["suffix", "string"], ends_with)
+        .with("String.replace", &["before", "after", "string"], replace)
+        .with("String.toNumber", &["string"], to_number)
+        .with("String.fromNumber", &["number"], from_number)
+}
+
+fn length(args: Vec<Value>, _: &dyn Runtime) -> Result<Value> {
+    match &args[..] {
+        [Value::String(s)] => Ok(Number::from(s.chars().count() as u64).into()),
+        _ => Err(Error::InvalidArguments("String.length".into(), args)),
+    }
+}
+
+fn slice(args: Vec<Value>, _: &dyn Runtime) -> Result<Value> {
+    let bounds = match &args[..] {
+        [Value::Number(start), Value::Number(end), Value::String(s)] => {
+            start.as_i64().zip(end.as_i64()).map(|b| (b, s))
+        }
+        _ => None,
+    };
+    match bounds {
+        Some(((start, end), s)) => {
+            let len = s.chars().count() as i64;
+            let index = |i: i64| if i < 0 { (len + i).max(0) } else { i.min(len) };
+            let (start, end) = (index(start), index(end));
+            let len = (end - start).max(0) as usize;
+            Ok(s.chars()
+                .skip(start as usize)
+                .take(len)
+                .collect::<String>()
+                .into())
+        }
+        None => Err(Error::InvalidArguments("String.slice".into(), args)),
+    }
+}
+
+fn split(args: Vec<Value>, _: &dyn Runtime) -> Result<Value> {
+    match &args[..] {
+        [Value::String(sep), Value::String(s)] if sep.is_empty() => Ok(s
+            .chars()
+            .map(|c| c.to_string().into())
+            .collect::<Vec<Value>>()
+            .into()),
+        [Value::String(sep), Value::String(s)] => Ok(s
+            .split(sep.as_str())
+            .map(|p| p.to_string().into())
+            .collect::<Vec<Value>>()
+            .into()),
+        _ => Err(Error::InvalidArguments("String.split".into(), args)),
+    }
+}
+
+fn join(args: Vec<Value>, _: &dyn Runtime) -> Result<Value> {
+    let parts = match &args[..] {
+        [Value::String(sep), Value::List(list)] => list
+            .iter()
+            .map(|v| match v {
+                Value::String(s) => Some(s.as_str()),
+                _ => None,
+            })
+            .collect::<Option<Vec<&str>>>()
+            .map(|parts| parts.join(sep)),
+        _ => None,
+    };
+    match parts {
+        Some(s) => Ok(s.into()),
+        None => Err(Error::InvalidArguments("String.join".into(), args)),
+    }
+}
+
+fn trim(args: Vec<Value>, _: &dyn Runtime) -> Result<Value> {
+    match &args[..] {
+        [Value::String(s)] => Ok(s.trim().to_string().into()),
+        _ => Err(Error::InvalidArguments("String.trim".into(), args)),
+    }
+}
+
+fn to_upper(args: Vec<Value>, _: &dyn Runtime) -> Result<Value> {
+    match &args[..] {
+        [Value::String(s)] => Ok(s.to_uppercase().into()),
+        _ => Err(Error::InvalidArguments("String.toUpper".into(), args)),
+    }
+}
+
+fn to_lower(args: Vec<Value>, _: &dyn Runtime) -> Result<Value> {
+    match &args[..] {
+        [Value::String(s)] => Ok(s.to_lowercase().into()),
+        _ => Err(Error::InvalidArguments("String.toLower".into(), args)),
+    }
+}
+
+fn contains(args: Vec<Value>, _: &dyn Runtime) -> Result<Value> {
+    match &args[..] {
+        [Value::String(part), Value::String(s)] => Ok(Value::Bool(s.contains(part.as_str()))),
+        _ => Err(Error::InvalidArguments("String.contains".into(), args)),
+    }
+}
+
+fn starts_with(args: Vec<Value>, _: &dyn Runtime) -> Result<Value> {
+    match &args[..] {
+        [Value::String(prefix), Value::String(s)] => {
+            Ok(Value::Bool(s.starts_with(prefix.as_str())))
+        }
+        _ => Err(Error::InvalidArguments("String.startsWith".into(), args)),
+    }
+}
+
+fn ends_with(args: Vec<Value>, _: &dyn Runtime) -> Result<Value> {
+    match &args[..] {
+        [Value::String(suffix), Value::String(s)] => Ok(Value::Bool(s.ends_with(suffix.as_str()))),
+        _ => Err(Error::InvalidArguments("String.endsWith".into(), args)),
+    }
+}
+
+fn replace(args: Vec<Value>, _: &dyn Runtime) -> Result<Value> {
+    match &args[..] {
+        [Value::String(before), Value::String(after), Value::String(s)] if !before.is_empty() => {
+            Ok(s.replace(before.as_str(), after).into())
+        }
+        _ => Err(Error::InvalidArguments("String.replace".into(), args)),
+    }
+}
+
+/// The number in the string, or null if there isn't one.
+fn to_number(args: Vec<Value>, _: &dyn Runtime) -> Result<Value> {
+    match &args[..] {
+        [Value::String(s)] => {
+            let s = s.trim();
+            let number = if let Ok(i) = s.parse::<i64>() {
+                Some(Number::from(i))
+            } else if let Ok(u) = s.parse::<u64>() {
+                Some(Number::from(u))
+            } else {
+                s.parse::<f64>()
+                    .ok()
+                    .filter(|f| f.is_finite())
+                    .map(Number::from)
+            };
+            Ok(number.map(Value::Number).unwrap_or_default())
+        }
+        _ => Err(Error::InvalidArguments("String.toNumber".into(), args)),
+    }
+}
+
+fn from_number(args: Vec<Value>, _: &dyn Runtime) -> Result<Value> {
+    match &args[..] {
+        [Value::Number(n)] => Ok(n.to_string().into()),
+        _ => Err(Error::InvalidArguments("String.fromNumber".into(), args)),
+    }
+}
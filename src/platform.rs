@@ -1,8 +1,28 @@
-use crate::{vm, Env, Error, Result, Value};
+use crate::{vm, Env, Error, Result, Runtime, Value};
+use indexmap::IndexSet;
+use std::fmt;
+use std::sync::Arc;
 
-pub trait Platform: Sized {
+#[cfg(feature = "fs")]
+mod fs;
+
+#[cfg(feature = "fs")]
+pub use fs::FsPlatform;
+
+pub trait Platform {
     fn init(&self, state: &mut vm::State) -> Result<()>;
-    fn call(&self, env: Env, name: &str, arg: Value) -> Result<Value>;
+
+    /// Handle `:platform: name`, called with the bindings where it's used.
+    /// `rt` calls back into the evaluation, e.g. to call a function passed
+    /// in `arg`.
+    fn call(&self, env: Env, name: &str, arg: Value, rt: &dyn Runtime) -> Result<Value>;
+
+    /// The names `call` handles, for tools to list, and for a
+    /// `PlatformStack` to choose which platform to call. A platform that
+    /// doesn't list any is sent every name in its namespace.
+    fn names(&self) -> Vec<String> {
+        vec![]
+    }
 }
 
 #[derive(Default, Debug)]
@@ -13,10 +33,193 @@ impl Platform for DefaultPlatform {
         Ok(())
     }
 
-    fn call(&self, _env: Env, name: &str, _: Value) -> Result<Value> {
+    fn call(&self, _env: Env, name: &str, _: Value, _: &dyn Runtime) -> Result<Value> {
         Err(Error::PlatformCallError(format!(
             "cannot call {} in this platform",
             name
         )))
     }
 }
+
+/// Several platforms in one, each under its own namespace, e.g. `fs.read`
+/// calls `read` in the platform added with the namespace `fs`.
+///
+/// A name is handled by the first platform added that lists it in `names`,
+/// or that doesn't list any names and has the name's namespace, and every
+/// platform is initialized, in the order they were added. A platform without
+/// names added with `with_global` handles every name that gets to it.
+#[derive(Default)]
+pub struct PlatformStack {
+    layers: Vec<Layer>,
+}
+
+struct Layer {
+    namespace: Option<String>,
+    platform: Box<dyn Platform>,
+}
+
+impl PlatformStack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a platform whose names are used as `namespace.name`.
+    pub fn with<S, P>(mut self, namespace: S, platform: P) -> Self
+    where
+        S: Into<String>,
+        P: Platform + 'static,
+    {
+        self.layers.push(Layer {
+            namespace: Some(namespace.into()),
+            platform: Box::new(platform),
+        });
+        self
+    }
+
+    /// Add a platform whose names are used as they are.
+    pub fn with_global<P>(mut self, platform: P) -> Self
+    where
+        P: Platform + 'static,
+    {
+        self.layers.push(Layer {
+            namespace: None,
+            platform: Box::new(platform),
+        });
+        self
+    }
+
+    /// The platform handling `name`, and the name it knows it by.
+    fn find<'a>(&self, name: &'a str) -> Option<(&dyn Platform, &'a str)> {
+        self.layers.iter().find_map(|layer| {
+            let local = match &layer.namespace {
+                Some(ns) => name.strip_prefix(ns.as_str())?.strip_prefix('.')?,
+                None => name,
+            };
+            let names = layer.platform.names();
+            if names.is_empty() || names.iter().any(|n| n == local) {
+                Some((layer.platform.as_ref(), local))
+            } else {
+                None
+            }
+        })
+    }
+}
+
+impl Platform for PlatformStack {
+    fn init(&self, state: &mut vm::State) -> Result<()> {
+        self.layers
+            .iter()
+            .try_for_each(|layer| layer.platform.init(state))
+    }
+
+    fn call(&self, env: Env, name: &str, arg: Value, rt: &dyn Runtime) -> Result<Value> {
+        match self.find(name) {
+            Some((platform, local)) => platform.call(env, local, arg, rt),
+            None => DefaultPlatform.call(env, name, arg, rt),
+        }
+    }
+
+    /// Every name, with its namespace, in order of precedence.
+    fn names(&self) -> Vec<String> {
+        let names: IndexSet<String> = self
+            .layers
+            .iter()
+            .flat_map(|layer| {
+                layer
+                    .platform
+                    .names()
+                    .into_iter()
+                    .map(move |name| match &layer.namespace {
+                        Some(ns) => format!("{}.{}", ns, name),
+                        None => name,
+                    })
+            })
+            .collect();
+        names.into_iter().collect()
+    }
+}
+
+impl fmt::Debug for PlatformStack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.layers.iter().map(|l| &l.namespace))
+            .finish()
+    }
+}
+
+/// The platform calls scripts are allowed to make, by name or by namespace:
+/// `fs` allows `fs.read` and `fs.write`, `fs.read` allows only that.
+/// `:import` allows importing files.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Capabilities {
+    /// `None` allows everything.
+    allowed: Option<IndexSet<String>>,
+}
+
+impl Capabilities {
+    /// Every platform call.
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    /// No platform calls at all.
+    pub fn none() -> Self {
+        Self {
+            allowed: Some(IndexSet::new()),
+        }
+    }
+
+    /// Allow a name, or every name in a namespace.
+    pub fn with<S>(mut self, name: S) -> Self
+    where
+        S: Into<String>,
+    {
+        if let Some(allowed) = &mut self.allowed {
+            allowed.insert(name.into());
+        }
+        self
+    }
+
+    pub fn allows(&self, name: &str) -> bool {
+        match &self.allowed {
+            None => true,
+            Some(allowed) => allowed.iter().any(|a| {
+                name.strip_prefix(a.as_str())
+                    .map(|rest| rest.is_empty() || rest.starts_with('.'))
+                    .unwrap_or(false)
+            }),
+        }
+    }
+}
+
+type AuditFn = dyn Fn(&str, &Value) + Send + Sync;
+
+/// Called with every platform call that's allowed, and its argument, before
+/// it's made.
+#[derive(Clone)]
+pub struct Audit(Arc<AuditFn>);
+
+impl Audit {
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(&str, &Value) + Send + Sync + 'static,
+    {
+        Self(Arc::new(f))
+    }
+
+    pub fn record(&self, name: &str, arg: &Value) {
+        (self.0)(name, arg)
+    }
+}
+
+impl PartialEq for Audit {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl fmt::Debug for Audit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Audit")
+    }
+}
@@ -0,0 +1,259 @@
+use super::Platform;
+use crate::{vm, yaml, Env, Error, Expr, Result, Runtime, Value};
+use serde_json as json;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Read-only access to the files in a directory, for a `PlatformStack`,
+/// usually under the namespace `fs`:
+///
+/// - `read`: the text of a file.
+/// - `readYaml`, `readJson`: a file parsed into a value.
+/// - `list`: the paths of the entries of a directory.
+/// - `glob`: the paths matching a pattern, like `config/**/*.yml`.
+/// - `exists`: whether there's a file or directory at the path.
+///
+/// Paths are relative to the directory, and can't go outside of it, not even
+/// through symbolic links. The paths it returns are relative to it too.
+///
+/// Every call gives a `Result` of the standard library, with the error
+/// message if it fails.
+#[derive(Debug, Clone)]
+pub struct FsPlatform {
+    root: PathBuf,
+}
+
+impl FsPlatform {
+    /// Give access to the files in `root`, which must be a directory.
+    pub fn new<P>(root: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let root = root.as_ref();
+        let root = root
+            .canonicalize()
+            .map_err(|e| Error::IoError(root.display().to_string(), e))?;
+        Ok(Self { root })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The real path of `path`, if it exists and is inside the root.
+    fn resolve(&self, path: &str) -> std::result::Result<Option<PathBuf>, String> {
+        let mut depth = 0;
+        for component in Path::new(path).components() {
+            match component {
+                Component::Normal(_) => depth += 1,
+                Component::CurDir => {}
+                Component::ParentDir if depth > 0 => depth -= 1,
+                _ => return Err(format!("{} is outside of the root directory", path)),
+            }
+        }
+
+        match self.root.join(path).canonicalize() {
+            Ok(real) if real.starts_with(&self.root) => Ok(Some(real)),
+            Ok(_) => Err(format!("{} is outside of the root directory", path)),
+            Err(_) => Ok(None),
+        }
+    }
+
+    fn existing(&self, path: &str) -> std::result::Result<PathBuf, String> {
+        self.resolve(path)?
+            .ok_or_else(|| format!("{} does not exist", path))
+    }
+
+    fn read(&self, path: &str) -> std::result::Result<String, String> {
+        fs::read_to_string(self.existing(path)?).map_err(|e| format!("cannot read {}: {}", path, e))
+    }
+
+    fn list(&self, path: &str) -> std::result::Result<Vec<Value>, String> {
+        let dir = self.existing(path)?;
+        let entries = fs::read_dir(&dir).map_err(|e| format!("cannot list {}: {}", path, e))?;
+        let mut paths = entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| self.inside(p))
+            .filter_map(|p| self.relative(&p))
+            .collect::<Vec<String>>();
+        paths.sort();
+        Ok(paths.into_iter().map(Value::from).collect())
+    }
+
+    /// Fails with the VM's error if walking the directory goes over its
+    /// limits.
+    fn glob(
+        &self,
+        pattern: &str,
+        rt: &dyn Runtime,
+    ) -> Result<std::result::Result<Vec<Value>, String>> {
+        if let Err(e) = self.resolve_pattern(pattern) {
+            return Ok(Err(e));
+        }
+        let pattern: Vec<&str> = pattern.split('/').filter(|p| !p.is_empty()).collect();
+        let mut paths = vec![];
+        self.walk(&self.root, &mut paths, rt)?;
+        let mut paths = paths
+            .into_iter()
+            .filter(|p| {
+                let segments: Vec<&str> = p.split('/').collect();
+                matches_path(&pattern, &segments)
+            })
+            .collect::<Vec<String>>();
+        paths.sort();
+        Ok(Ok(paths.into_iter().map(Value::from).collect()))
+    }
+
+    /// Patterns follow the same rules as paths.
+    fn resolve_pattern(&self, pattern: &str) -> std::result::Result<(), String> {
+        if Path::new(pattern).is_absolute() || pattern.split('/').any(|p| p == "..") {
+            Err(format!("{} is outside of the root directory", pattern))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Every path under `dir`, relative to the root, without following links
+    /// out of it. Each entry is a step of the evaluation.
+    fn walk(&self, dir: &Path, paths: &mut Vec<String>, rt: &dyn Runtime) -> Result<()> {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return Ok(()),
+        };
+        for entry in entries.filter_map(|e| e.ok()) {
+            rt.step()?;
+            let path = entry.path();
+            let real = match path.canonicalize() {
+                Ok(real) if real.starts_with(&self.root) => real,
+                _ => continue,
+            };
+            if let Some(relative) = self.relative(&path) {
+                paths.push(relative);
+            }
+            // Links to directories are listed, but not walked into, so a
+            // link to a parent can't make it go round in circles.
+            let is_link = entry.file_type().map(|t| t.is_symlink()).unwrap_or(true);
+            if real.is_dir() && !is_link {
+                self.walk(&path, paths, rt)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether `path`, following links, is inside the root.
+    fn inside(&self, path: &Path) -> bool {
+        path.canonicalize()
+            .map(|real| real.starts_with(&self.root))
+            .unwrap_or(false)
+    }
+
+    fn relative(&self, path: &Path) -> Option<String> {
+        let relative = path.strip_prefix(&self.root).ok()?;
+        let parts: Vec<String> = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        Some(parts.join("/"))
+    }
+}
+
+impl Platform for FsPlatform {
+    fn init(&self, _state: &mut vm::State) -> Result<()> {
+        Ok(())
+    }
+
+    fn call(&self, _env: Env, name: &str, arg: Value, rt: &dyn Runtime) -> Result<Value> {
+        let path = match &arg {
+            Value::String(path) => path.as_str(),
+            _ => return Err(Error::InvalidArguments(name.into(), vec![arg])),
+        };
+
+        let res = match name {
+            "read" => self.read(path).map(Value::from),
+            "readYaml" => self.read(path).and_then(|s| {
+                yaml::from_str::<Value>(&s).map_err(|e| format!("cannot parse {}: {}", path, e))
+            }),
+            "readJson" => self.read(path).and_then(|s| {
+                json::from_str::<Value>(&s).map_err(|e| format!("cannot parse {}: {}", path, e))
+            }),
+            "list" => self.list(path).map(Value::from),
+            "glob" => self.glob(path, rt)?.map(Value::from),
+            "exists" => self.resolve(path).map(|p| p.is_some().into()),
+            _ => return Err(Error::InvalidArguments(name.into(), vec![arg])),
+        };
+
+        let (result, value) = match res {
+            Ok(value) => ("Result.ok", value),
+            Err(message) => ("Result.err", message.into()),
+        };
+        let expr = Expr::Call(vec![Expr::Module(result.into()), Expr::Value(value)]);
+        rt.eval(&expr, Env::new())
+    }
+
+    fn names(&self) -> Vec<String> {
+        ["read", "readYaml", "readJson", "list", "glob", "exists"]
+            .iter()
+            .map(|n| n.to_string())
+            .collect()
+    }
+}
+
+/// Whether the segments of a path match those of a pattern, where `**`
+/// matches any number of segments.
+fn matches_path(pattern: &[&str], path: &[&str]) -> bool {
+    matches(
+        pattern,
+        path,
+        |p| *p == "**",
+        |p, s| {
+            let p: Vec<char> = p.chars().collect();
+            let s: Vec<char> = s.chars().collect();
+            matches_segment(&p, &s)
+        },
+    )
+}
+
+/// Whether a segment matches a pattern, where `*` matches any characters and
+/// `?` any one character.
+fn matches_segment(pattern: &[char], segment: &[char]) -> bool {
+    matches(pattern, segment, |p| *p == '*', |p, c| *p == '?' || p == c)
+}
+
+/// Whether `items` match `pattern`, where the stars of the pattern match any
+/// number of items, and everything else one item it `fits`.
+///
+/// A star only ever needs to take one more item when what follows it doesn't
+/// match, going back to the last star alone, so it takes at most
+/// `pattern.len() * items.len()` steps, not exponentially many.
+fn matches<P, T>(
+    pattern: &[P],
+    items: &[T],
+    is_star: impl Fn(&P) -> bool,
+    fits: impl Fn(&P, &T) -> bool,
+) -> bool {
+    let (mut p, mut i) = (0, 0);
+    // The last star, and the items it has taken up to.
+    let mut star = None;
+    while i < items.len() {
+        match pattern.get(p) {
+            Some(s) if is_star(s) => {
+                star = Some((p, i));
+                p += 1;
+            }
+            Some(x) if fits(x, &items[i]) => {
+                p += 1;
+                i += 1;
+            }
+            _ => match star {
+                Some((s, taken)) => {
+                    star = Some((s, taken + 1));
+                    p = s + 1;
+                    i = taken + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(is_star)
+}
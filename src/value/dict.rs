@@ -0,0 +1,159 @@
+use crate::value::Record;
+use crate::yaml::Value as Yaml;
+use crate::{Error, Result, Value};
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+
+/// A mapping with keys of any kind, except functions, ordered by key.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Dict(Vec<(Value, Value)>);
+
+impl Dict {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, key: &Value) -> Option<&Value> {
+        self.find(key).ok().map(|i| &self.0[i].1)
+    }
+
+    pub fn contains_key(&self, key: &Value) -> bool {
+        self.find(key).is_ok()
+    }
+
+    /// Add an entry, replacing the one with the same key. Fails if the key is
+    /// a function, contains one, or is `NaN`.
+    pub fn insert(&mut self, key: Value, value: Value) -> Result<()> {
+        if !is_key(&key) {
+            return Err(Error::InvalidKey(key));
+        }
+        match self.find(&key) {
+            Ok(i) => self.0[i].1 = value,
+            Err(i) => self.0.insert(i, (key, value)),
+        }
+        Ok(())
+    }
+
+    pub fn remove(&mut self, key: &Value) -> Option<Value> {
+        self.find(key).ok().map(|i| self.0.remove(i).1)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The entries, ordered by key.
+    pub fn iter(&self) -> impl Iterator<Item = (&Value, &Value)> {
+        self.0.iter().map(|(k, v)| (k, v))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &Value> {
+        self.0.iter().map(|(k, _)| k)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Value> {
+        self.0.iter().map(|(_, v)| v)
+    }
+
+    fn find(&self, key: &Value) -> std::result::Result<usize, usize> {
+        self.0.binary_search_by(|(k, _)| order(k, key))
+    }
+}
+
+impl TryFrom<Yaml> for Dict {
+    type Error = Error;
+
+    fn try_from(yaml: Yaml) -> Result<Self> {
+        match yaml {
+            Yaml::Mapping(m) => {
+                let mut dict = Self::new();
+                for (k, v) in m {
+                    dict.insert(k.into(), v.into())?;
+                }
+                Ok(dict)
+            }
+            y => Err(Error::NotADict(y.into())),
+        }
+    }
+}
+
+impl TryFrom<Record> for Dict {
+    type Error = Error;
+
+    /// The record's fields, with the keys that aren't strings, like `$1`,
+    /// turned back into what they were in YAML.
+    fn try_from(record: Record) -> Result<Self> {
+        let mut dict = Self::new();
+        for (k, v) in record.iter() {
+            dict.insert(Record::de_field_name(k)?.into(), v.clone())?;
+        }
+        Ok(dict)
+    }
+}
+
+impl Serialize for Dict {
+    fn serialize<S>(&self, s: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = s.serialize_map(Some(self.0.len()))?;
+        for (k, v) in self.iter() {
+            map.serialize_entry(k, v)?;
+        }
+        map.end()
+    }
+}
+
+fn is_key(value: &Value) -> bool {
+    match value {
+        Value::Function(_) => false,
+        Value::Number(n) => !n.as_f64().map(f64::is_nan).unwrap_or(false),
+        Value::List(l) => l.iter().all(is_key),
+        Value::Record(r) => r.values().all(is_key),
+        Value::Dict(d) => d.values().all(is_key),
+        _ => true,
+    }
+}
+
+/// Order keys of any kind: first by kind, then like `Value::compare`.
+fn order(a: &Value, b: &Value) -> Ordering {
+    let rank = |v: &Value| match v {
+        Value::Null => 0,
+        Value::Bool(_) => 1,
+        Value::Number(_) => 2,
+        Value::String(_) => 3,
+        Value::List(_) => 4,
+        Value::Record(_) => 5,
+        Value::Dict(_) => 6,
+        Value::Function(_) => 7,
+    };
+    match (a, b) {
+        (Value::List(l1), Value::List(l2)) => l1
+            .iter()
+            .zip(l2.iter())
+            .map(|(v1, v2)| order(v1, v2))
+            .find(|o| *o != Ordering::Equal)
+            .unwrap_or_else(|| l1.len().cmp(&l2.len())),
+        (Value::Record(r1), Value::Record(r2)) => r1
+            .sorted()
+            .into_iter()
+            .zip(r2.sorted())
+            .map(|((k1, v1), (k2, v2))| k1.cmp(k2).then_with(|| order(v1, v2)))
+            .find(|o| *o != Ordering::Equal)
+            .unwrap_or_else(|| r1.len().cmp(&r2.len())),
+        (Value::Dict(d1), Value::Dict(d2)) => d1
+            .iter()
+            .zip(d2.iter())
+            .map(|((k1, v1), (k2, v2))| order(k1, k2).then_with(|| order(v1, v2)))
+            .find(|o| *o != Ordering::Equal)
+            .unwrap_or_else(|| d1.len().cmp(&d2.len())),
+        _ => rank(a)
+            .cmp(&rank(b))
+            .then_with(|| a.compare(b).unwrap_or(Ordering::Equal)),
+    }
+}
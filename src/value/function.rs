@@ -1,15 +1,21 @@
+use crate::compile::{self, Lambda, Scope, Scopes, Tail};
 use crate::platform::Platform;
+use crate::vm::Context;
 use crate::Env;
 use crate::Expr;
 use crate::Value;
-use crate::{Error, Result as CrateResult};
+use crate::{Error, Frame, Result as CrateResult};
 use serde::ser::{Error as SerdeError, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::fmt;
+use std::sync::Arc;
 
-#[derive(Debug, Clone, PartialEq, Default)]
+#[derive(Clone)]
 pub struct Function {
-    pub(crate) env: Env,
-    pub(crate) args: Vec<String>,
-    pub(crate) expr: Expr,
+    pub(crate) scope: Scope,
+    pub(crate) lambda: Arc<Lambda>,
+    /// Arguments of a partially applied function.
+    pub(crate) applied: Vec<Value>,
 }
 
 impl Function {
@@ -17,10 +23,23 @@ impl Function {
     where
         I: IntoIterator<Item = String>,
     {
+        let params: Arc<[String]> = args.into_iter().collect();
+        let body = Scopes::default().nested(Some(params.clone()), |s| expr.compile(s));
+        Self::from_lambda(
+            Arc::new(Lambda {
+                params,
+                body,
+                name: None,
+            }),
+            Scope::new(env),
+        )
+    }
+
+    pub(crate) fn from_lambda(lambda: Arc<Lambda>, scope: Scope) -> Self {
         Self {
-            env,
-            args: args.into_iter().collect(),
-            expr,
+            scope,
+            lambda,
+            applied: vec![],
         }
     }
 
@@ -29,35 +48,73 @@ impl Function {
         I: IntoIterator<Item = (String, Expr)>,
     {
         for (k, v) in env {
-            self.env.insert(k, v);
+            self.scope.env.insert(k, v);
         }
         self
     }
 
-    pub fn call<I, P>(mut self, args: I, platform: &P) -> CrateResult<Value>
+    /// The name the function was bound to where it was defined, if any.
+    pub fn name(&self) -> Option<&str> {
+        self.lambda.name.as_deref()
+    }
+
+    /// The names of the arguments the function is still waiting for.
+    pub fn args(&self) -> &[String] {
+        &self.lambda.params[self.applied.len()..]
+    }
+
+    pub fn call<I, P>(self, args: I, ctx: &Context<P>) -> CrateResult<Value>
     where
         I: IntoIterator<Item = CrateResult<Value>>,
         P: Platform,
     {
-        let mut args = args.into_iter();
-        if let Some((name, rest)) = self.args.split_first() {
-            if let Some(arg) = args.next() {
-                self.env.insert(name.into(), arg?.into());
-                self.args = rest.to_vec();
-                self.call(args, platform)
-            } else {
-                Ok(Value::Function(Box::new(self)))
-            }
-        } else {
-            if args.count() == 0 {
-                self.expr.eval(self.env, platform)
-            } else {
-                Err(Error::Undefined("".into()))
+        let args = args.into_iter().collect::<CrateResult<Vec<Value>>>()?;
+        compile::run(self.enter(args)?, ctx)
+    }
+
+    /// Call the function with the arguments recorded in the frame, adding the
+    /// frame to the stack trace of any error raised by the function body.
+    pub fn call_traced<P>(self, frame: Frame, ctx: &Context<P>) -> CrateResult<Value>
+    where
+        P: Platform,
+    {
+        compile::run(Tail::Call(Box::new(self), frame), ctx)
+    }
+
+    /// Bind the arguments, returning either the body to evaluate, or a
+    /// function waiting for the rest of the arguments.
+    pub(crate) fn enter(mut self, args: Vec<Value>) -> CrateResult<Tail> {
+        self.applied.extend(args);
+        match self.applied.len().cmp(&self.lambda.params.len()) {
+            Ordering::Less => Ok(Tail::Value(self.into())),
+            Ordering::Equal => {
+                let scope = self
+                    .scope
+                    .with_args(self.lambda.params.clone(), self.applied);
+                Ok(Tail::Eval(self.lambda.body.clone(), scope))
             }
+            Ordering::Greater => Err(Error::Undefined("".into())),
         }
     }
 }
 
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.lambda, &other.lambda)
+            && self.applied == other.applied
+            && self.scope == other.scope
+    }
+}
+
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Function")
+            .field("args", &self.args())
+            .field("scope", &self.scope)
+            .finish()
+    }
+}
+
 impl Serialize for Function {
     fn serialize<S>(&self, _: S) -> Result<S::Ok, S::Error>
     where
@@ -1,12 +1,12 @@
+mod dict;
 mod function;
 mod list;
 mod record;
+#[allow(clippy::module_inception)]
 mod value;
 
-pub use crate::yaml::{
-    from_reader, from_slice, from_str, from_value as from_yaml, to_string, to_value as to_yaml,
-    to_vec, to_writer, Mapping, Number,
-};
+pub use crate::yaml::Number;
+pub use dict::Dict;
 pub use function::Function;
 pub use list::List;
 pub use record::Record;
@@ -71,6 +71,14 @@ impl Record {
         }
     }
 
+    /// The fields, ordered by name rather than in definition order, like
+    /// equality sees them.
+    pub fn sorted(&self) -> Vec<(&String, &Value)> {
+        let mut fields: Vec<(&String, &Value)> = self.iter().collect();
+        fields.sort_by_key(|(k, _)| *k);
+        fields
+    }
+
     pub fn get_from_yaml(&self, field: &Yaml) -> Result<&Value> {
         let field_ = Self::ser_field_name(field);
         if let Some(val) = self.get(&field_) {
@@ -1,23 +1,26 @@
-use crate::value::{Function, List, Number, Record};
+use crate::value::{Dict, Function, List, Number, Record};
 use crate::yaml;
 use crate::yaml::Value as Yaml;
 use crate::{Error, Result};
 use indexmap::IndexMap;
 use serde::ser::{Serialize, Serializer};
 use serde::Deserialize;
+use std::cmp::Ordering;
 use std::convert::TryInto;
 use std::fmt;
 use std::result;
 
-#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
 #[serde(from = "Yaml")]
 pub enum Value {
+    #[default]
     Null,
     Bool(bool),
     Number(Number),
     String(String),
     List(List),
     Record(Record),
+    Dict(Dict),
     Function(Box<Function>),
 }
 
@@ -33,15 +36,15 @@ impl From<Record> for Value {
     }
 }
 
-impl From<List> for Value {
-    fn from(v: List) -> Self {
-        Self::List(v)
+impl From<Dict> for Value {
+    fn from(v: Dict) -> Self {
+        Self::Dict(v)
     }
 }
 
-impl Default for Value {
-    fn default() -> Self {
-        Self::Null
+impl From<List> for Value {
+    fn from(v: List) -> Self {
+        Self::List(v)
     }
 }
 
@@ -75,6 +78,30 @@ impl From<Number> for Value {
     }
 }
 
+impl From<&str> for Value {
+    fn from(v: &str) -> Self {
+        Self::String(v.into())
+    }
+}
+
+impl From<i64> for Value {
+    fn from(v: i64) -> Self {
+        Self::Number(v.into())
+    }
+}
+
+impl From<u64> for Value {
+    fn from(v: u64) -> Self {
+        Self::Number(v.into())
+    }
+}
+
+impl From<f64> for Value {
+    fn from(v: f64) -> Self {
+        Self::Number(v.into())
+    }
+}
+
 impl From<bool> for Value {
     fn from(v: bool) -> Self {
         Self::Bool(v)
@@ -85,7 +112,7 @@ impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Value::Null => write!(f, "null"),
-            Value::Function(fun) => write!(f, "ƒ({})", fun.args.join(", ")),
+            Value::Function(fun) => write!(f, "ƒ({})", fun.args().join(", ")),
             Value::Bool(b) => b.fmt(f),
             Value::Number(n) => n.fmt(f),
             Value::String(s) => write!(f, "{:?}", s),
@@ -111,6 +138,17 @@ impl fmt::Display for Value {
                 }
                 write!(f, "}}")
             }
+            Value::Dict(d) => {
+                let len = d.len();
+                write!(f, "{{")?;
+                for (i, (k, v)) in d.iter().enumerate() {
+                    write!(f, "{}: {}", k, v)?;
+                    if i + 1 != len {
+                        write!(f, ", ")?;
+                    }
+                }
+                write!(f, "}}")
+            }
         }
     }
 }
@@ -127,6 +165,7 @@ impl Serialize for Value {
             Value::String(v) => s.serialize_str(v),
             Value::List(v) => v.serialize(s),
             Value::Record(v) => v.serialize(s),
+            Value::Dict(v) => v.serialize(s),
             Value::Function(v) => v.serialize(s),
         }
     }
@@ -165,6 +204,70 @@ impl TryInto<Yaml> for Value {
 }
 
 impl Value {
+    /// Order two values of the same kind.
+    ///
+    /// Numbers compare by value, so integers and floats can be mixed.
+    /// Strings compare by unicode code points. Lists, records and dicts
+    /// compare lexicographically, records field by field (name, then value)
+    /// in name order, and dicts entry by entry in key order. `false` is less
+    /// than `true`. Values of different kinds, functions and `NaN`
+    /// cannot be ordered.
+    pub fn compare(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Self::Null, Self::Null) => Some(Ordering::Equal),
+            (Self::Bool(b1), Self::Bool(b2)) => Some(b1.cmp(b2)),
+            (Self::Number(n1), Self::Number(n2)) => {
+                let int = |n: &Number| {
+                    n.as_i64()
+                        .map(i128::from)
+                        .or_else(|| n.as_u64().map(i128::from))
+                };
+                if let (Some(i1), Some(i2)) = (int(n1), int(n2)) {
+                    Some(i1.cmp(&i2))
+                } else {
+                    n1.as_f64()?.partial_cmp(&n2.as_f64()?)
+                }
+            }
+            (Self::String(s1), Self::String(s2)) => Some(s1.cmp(s2)),
+            (Self::List(l1), Self::List(l2)) => {
+                for (v1, v2) in l1.iter().zip(l2.iter()) {
+                    match v1.compare(v2)? {
+                        Ordering::Equal => {}
+                        ord => return Some(ord),
+                    }
+                }
+                Some(l1.len().cmp(&l2.len()))
+            }
+            (Self::Record(r1), Self::Record(r2)) => {
+                for ((k1, v1), (k2, v2)) in r1.sorted().into_iter().zip(r2.sorted()) {
+                    match k1.cmp(k2) {
+                        Ordering::Equal => {}
+                        ord => return Some(ord),
+                    }
+                    match v1.compare(v2)? {
+                        Ordering::Equal => {}
+                        ord => return Some(ord),
+                    }
+                }
+                Some(r1.len().cmp(&r2.len()))
+            }
+            (Self::Dict(d1), Self::Dict(d2)) => {
+                for ((k1, v1), (k2, v2)) in d1.iter().zip(d2.iter()) {
+                    match k1.compare(k2)? {
+                        Ordering::Equal => {}
+                        ord => return Some(ord),
+                    }
+                    match v1.compare(v2)? {
+                        Ordering::Equal => {}
+                        ord => return Some(ord),
+                    }
+                }
+                Some(d1.len().cmp(&d2.len()))
+            }
+            _ => None,
+        }
+    }
+
     pub fn get_from_yaml_nested<I>(&self, fields: I) -> Result<&Value>
     where
         I: IntoIterator<Item = Result<Yaml>>,
@@ -1,5 +1,15 @@
-use crate::platform::Platform;
-use crate::{yaml, Env, Error, Expr, Result, Value};
+use crate::env::Memo;
+use crate::loader::Origin;
+use crate::module::{Modules, Prelude};
+use crate::native::{IntoNative, Natives};
+use crate::platform::{Audit, Capabilities, Platform};
+use crate::{CompiledExpr, Env, Error, Expr, Result, Value};
+use indexmap::IndexMap;
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
 #[derive(Default, Debug, PartialEq)]
 pub struct State {
@@ -12,55 +22,178 @@ impl State {
     }
 }
 
+/// What to do when integer arithmetic overflows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overflow {
+    /// Fall back to floating point arithmetic.
+    #[default]
+    Promote,
+    /// Fail with `Error::ArithmeticOverflow`.
+    Error,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    pub overflow: Overflow,
+
+    /// Maximum number of evaluation steps.
+    pub max_steps: Option<u64>,
+
+    /// Maximum nesting of evaluations. Calls in tail position don't count,
+    /// and calls back from native functions, like the function `List.map`
+    /// applies, count twice.
+    ///
+    /// Each level can take up to 30KB of stack in a debug build, and 3KB in
+    /// a release build, so on a thread with a 2MB stack, the default for
+    /// spawned threads, it should be at most 60 in a debug build and 600 in
+    /// a release build. Deeper evaluations need a thread with a bigger stack.
+    pub max_depth: Option<usize>,
+
+    /// Maximum time an evaluation can take.
+    pub timeout: Option<Duration>,
+
+    /// The platform calls scripts can make, and whether they can import
+    /// files.
+    pub capabilities: Capabilities,
+
+    /// The platform calls the code in some files can make, on top of
+    /// `capabilities`, by file.
+    pub module_capabilities: HashMap<String, Capabilities>,
+
+    /// Sees every platform call that's allowed.
+    pub audit: Option<Audit>,
+}
+
+/// Everything an evaluation needs from the VM running it.
+pub struct Context<'a, P: Platform> {
+    pub platform: &'a P,
+    pub config: &'a Config,
+    pub modules: &'a Modules,
+    pub natives: &'a Natives,
+    /// The names scripts start with, that imported files are evaluated in.
+    pub env: Env,
+    /// The values of the `:let` bindings evaluated so far.
+    pub(crate) memo: Memo,
+    /// The values of the VM's names evaluated so far.
+    pub(crate) globals: &'a Memo,
+    steps: Cell<u64>,
+    depth: Cell<usize>,
+    deadline: Option<Instant>,
+}
+
+impl<'a, P: Platform> Context<'a, P> {
+    /// Start a new evaluation, with fresh limits.
+    pub(crate) fn new(
+        platform: &'a P,
+        config: &'a Config,
+        modules: &'a Modules,
+        natives: &'a Natives,
+        env: Env,
+        globals: &'a Memo,
+    ) -> Self {
+        Self {
+            platform,
+            config,
+            modules,
+            natives,
+            env,
+            memo: Memo::default(),
+            globals,
+            steps: Cell::new(0),
+            depth: Cell::new(0),
+            deadline: config.timeout.map(|t| Instant::now() + t),
+        }
+    }
+
+    /// Number of evaluation steps taken so far.
+    pub fn steps(&self) -> u64 {
+        self.steps.get()
+    }
+
+    pub(crate) fn step(&self) -> Result<()> {
+        let steps = self.steps.get() + 1;
+        self.steps.set(steps);
+
+        if let Some(max) = self.config.max_steps {
+            if steps > max {
+                return Err(Error::StepLimitExceeded(max));
+            }
+        }
+
+        if let Some(deadline) = self.deadline {
+            if Instant::now() > deadline {
+                return Err(Error::Timeout(self.config.timeout.unwrap_or_default()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fail with `Error::CapabilityDenied` unless code from `from` can make
+    /// the platform call `name`, or import files with `:import`.
+    pub(crate) fn allow(&self, name: &str, from: Option<&Origin>) -> Result<()> {
+        let allowed = self.config.capabilities.allows(name)
+            && from
+                .into_iter()
+                .flat_map(Origin::files)
+                .filter_map(|f| self.config.module_capabilities.get(&**f))
+                .all(|c| c.allows(name));
+        if allowed {
+            Ok(())
+        } else {
+            Err(Error::CapabilityDenied(name.into()))
+        }
+    }
+
+    pub(crate) fn enter(&self) -> Result<()> {
+        let depth = self.depth.get() + 1;
+        if let Some(max) = self.config.max_depth {
+            if depth > max {
+                return Err(Error::DepthLimitExceeded(max));
+            }
+        }
+        self.depth.set(depth);
+        Ok(())
+    }
+
+    pub(crate) fn leave(&self) {
+        self.depth.set(self.depth.get() - 1);
+    }
+}
+
 pub struct Vm<P: Platform> {
     state: State,
+    config: Config,
     platform: P,
+    modules: Modules,
+    natives: Natives,
+    globals: Memo,
 }
 
 impl<P: Platform> Vm<P> {
-    pub fn new(platform: P) -> Result<Self>
-    where
-        P: Platform,
-    {
+    pub fn new(platform: P) -> Result<Self> {
+        Self::with_prelude(platform, Prelude::std())
+    }
+
+    /// Create a VM where scripts start with only the names in the prelude,
+    /// e.g. `Prelude::new()` for none at all.
+    pub fn with_prelude(platform: P, prelude: Prelude) -> Result<Self> {
         let mut state: State = Default::default();
 
-        // TODO: figure out how to split the lib.
-
-        let std = include_str!("./Yaml/Std.yaml");
-        let std: Expr = yaml::from_str(std)?;
-
-        state.set_env("Std".into(), std);
-
-        state.set_env("null_".into(), Expr::Variable("Std.null_".into()));
-        state.set_env("Bool".into(), Expr::Variable("Std.Bool".into()));
-        state.set_env("true_".into(), Expr::Variable("Std.Bool.true_".into()));
-        state.set_env("false_".into(), Expr::Variable("Std.Bool.false_".into()));
-        state.set_env("add".into(), Expr::Variable("Std.add".into()));
-        state.set_env("List".into(), Expr::Variable("Std.List".into()));
-        state.set_env("Maybe".into(), Expr::Variable("Std.Maybe".into()));
-        state.set_env("add".into(), Expr::Variable("Std.add".into()));
-        state.set_env("(+)".into(), Expr::Variable("Std.(+)".into()));
-        state.set_env("not".into(), Expr::Variable("Std.not".into()));
-        state.set_env("(!)".into(), Expr::Variable("Std.(!)".into()));
-        state.set_env("eq".into(), Expr::Variable("Std.(eq)".into()));
-        state.set_env("ne".into(), Expr::Variable("Std.(ne)".into()));
-        state.set_env("!=".into(), Expr::Variable("Std.(!=)".into()));
-        state.set_env("composeL".into(), Expr::Variable("Std.composeL".into()));
-        state.set_env("(<<)".into(), Expr::Variable("Std.(<<)".into()));
-        state.set_env("composeR".into(), Expr::Variable("Std.composeR".into()));
-        state.set_env("(>>)".into(), Expr::Variable("Std.(>>)".into()));
-        state.set_env("and".into(), Expr::Variable("Std.and".into()));
-        state.set_env("(&&)".into(), Expr::Variable("Std.(&&)".into()));
-        state.set_env("or".into(), Expr::Variable("Std.or".into()));
-        state.set_env("(||)".into(), Expr::Variable("Std.(||)".into()));
-        state.set_env("xor".into(), Expr::Variable("Std.xor".into()));
-        state.set_env("Maybe".into(), Expr::Variable("Std.Maybe".into()));
-        state.set_env("List".into(), Expr::Variable("Std.List".into()));
-        state.set_env("Rec".into(), Expr::Variable("Std.Rec".into()));
+        for (name, path) in prelude.iter() {
+            state.set_env(name.clone(), Expr::Module(path.clone()));
+        }
 
         platform.init(&mut state)?;
 
-        Ok(Self { platform, state })
+        Ok(Self {
+            platform,
+            state,
+            config: Default::default(),
+            modules: Modules::std(),
+            natives: Natives::std(),
+            globals: Memo::default(),
+        })
     }
 
     pub fn with_env<I>(mut self, env: I) -> Self
@@ -73,12 +206,164 @@ impl<P: Platform> Vm<P> {
         self
     }
 
+    pub fn with_overflow(mut self, overflow: Overflow) -> Self {
+        self.config.overflow = overflow;
+        self
+    }
+
+    pub fn with_max_steps(mut self, max_steps: u64) -> Self {
+        self.config.max_steps = Some(max_steps);
+        self
+    }
+
+    /// See `Config::max_depth` for how much stack it needs.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.config.max_depth = Some(max_depth);
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.config.timeout = Some(timeout);
+        self
+    }
+
+    /// Allow only these platform calls, e.g. `Capabilities::none()` for
+    /// none at all.
+    pub fn with_capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.config.capabilities = capabilities;
+        self
+    }
+
+    /// Allow the code of a module only these platform calls, of the ones
+    /// the VM allows. `module` is the name of a module added to the VM, or
+    /// the path of a file it imports, looked for like `:import` does from
+    /// a script that isn't in a file.
+    ///
+    /// Code the module imports, directly or not, can't do more than it can.
+    pub fn with_module_capabilities<S>(mut self, module: S, capabilities: Capabilities) -> Self
+    where
+        S: Into<String>,
+    {
+        let module = module.into();
+        let file = match self.modules.file(&module) {
+            Some(file) => file.to_string(),
+            None => self
+                .modules
+                .resolve(&module, None)
+                .map(|p| p.display().to_string())
+                .unwrap_or(module),
+        };
+        self.config.module_capabilities.insert(file, capabilities);
+        self
+    }
+
+    /// Call `f` with every platform call that's allowed, and its argument.
+    pub fn with_audit<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&str, &Value) + Send + Sync + 'static,
+    {
+        self.config.audit = Some(Audit::new(f));
+        self
+    }
+
+    /// Add a module, replacing any module with the same name. It's loaded
+    /// the first time it's used.
+    pub fn with_module<S, T>(mut self, name: S, source: T) -> Self
+    where
+        S: Into<String>,
+        T: Into<Cow<'static, str>>,
+    {
+        self.modules.insert(name.into(), source.into());
+        self
+    }
+
+    /// Add a directory to look for imported files in.
+    pub fn with_search_path<S>(mut self, path: S) -> Self
+    where
+        S: Into<PathBuf>,
+    {
+        self.modules = self.modules.with_search_path(path);
+        self
+    }
+
+    /// Replace all the modules, e.g. with `Modules::new()` for none at all.
+    pub fn with_modules(mut self, modules: Modules) -> Self {
+        self.modules = modules;
+        self
+    }
+
+    /// Replace all the native functions, e.g. with `Natives::new()` for none
+    /// at all.
+    pub fn with_natives(mut self, natives: Natives) -> Self {
+        self.natives = natives;
+        self
+    }
+
     pub fn set_env(&mut self, name: String, expr: Expr) {
         self.state.set_env(name, expr);
     }
 
+    /// Make a Rust function available to scripts by name. A dotted name like
+    /// `Http.get` makes it a field of the `Http` record, next to the other
+    /// functions registered in it, or the fields it already has, like those
+    /// of a module such as `List`.
+    ///
+    /// The function takes as many arguments as `f` does, and can be called
+    /// with fewer, like any other function. Arguments that can't be
+    /// converted fail the call with `Error::InvalidArguments`.
+    ///
+    /// Fails with `Error::NotARecord` if a name it would be a field of isn't
+    /// a record.
+    pub fn register_fn<S, F, A>(&mut self, name: S, f: F) -> Result<()>
+    where
+        S: Into<String>,
+        F: IntoNative<A>,
+    {
+        let name = name.into();
+        let mut path = name.split('.');
+        let root = path.next().unwrap_or_default().to_string();
+        let path: Vec<&str> = path.collect();
+        let current = match self.state.env.get(&root) {
+            Some(expr) if !path.is_empty() => Some(self.eval(expr.clone())?),
+            _ => None,
+        };
+
+        self.natives = std::mem::take(&mut self.natives).with_fn(name.as_str(), f);
+        let func = self.natives.get(&name).cloned().map(Value::from);
+        let value = with_field(current, &path, func.unwrap_or_default())?;
+        self.set_env(root, Expr::Value(value));
+        Ok(())
+    }
+
+    pub fn platform(&self) -> &P {
+        &self.platform
+    }
+
+    pub fn context(&self) -> Context<'_, P> {
+        Context::new(
+            &self.platform,
+            &self.config,
+            &self.modules,
+            &self.natives,
+            self.state.env.clone(),
+            &self.globals,
+        )
+    }
+
     pub fn eval(&self, expr: Expr) -> Result<Value> {
-        expr.eval(self.state.env.clone(), &self.platform)
+        self.eval_compiled(&CompiledExpr::new(&expr))
+    }
+
+    /// Evaluate an expression compiled ahead of time, e.g. one that's
+    /// evaluated many times.
+    pub fn eval_compiled(&self, expr: &CompiledExpr) -> Result<Value> {
+        self.eval_with(expr, &self.context())
+    }
+
+    /// Evaluate with the given context, e.g. to see how many steps it took
+    /// afterwards.
+    pub fn eval_with(&self, expr: &CompiledExpr, ctx: &Context<P>) -> Result<Value> {
+        expr.eval(self.state.env.clone(), ctx)
     }
 
     pub fn call<I>(&self, func: Value, args: I) -> Result<Value>
@@ -87,12 +372,30 @@ impl<P: Platform> Vm<P> {
     {
         match func {
             Value::Function(func) => {
+                let ctx = self.context();
                 let args = args
                     .into_iter()
-                    .map(|a| a.eval(self.state.env.clone(), &self.platform));
-                func.call(args, &self.platform)
+                    .map(|a| a.eval(self.state.env.clone(), &ctx));
+                func.call(args, &ctx)
             }
             _ => Err(Error::NotAFunction(func)),
         }
     }
 }
+
+/// `value` with `func` at the given path, adding records where needed.
+fn with_field(value: Option<Value>, path: &[&str], func: Value) -> Result<Value> {
+    match path.split_first() {
+        None => Ok(func),
+        Some((field, rest)) => {
+            let mut fields: IndexMap<String, Value> = match value {
+                Some(Value::Record(r)) => r.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+                Some(value) => return Err(Error::NotARecord(value)),
+                None => IndexMap::new(),
+            };
+            let inner = fields.get(*field).cloned();
+            fields.insert(field.to_string(), with_field(inner, rest, func)?);
+            Ok(fields.into())
+        }
+    }
+}